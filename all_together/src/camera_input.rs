@@ -1,13 +1,21 @@
-use std::sync::{
-    mpsc::{Receiver, Sender},
-    Arc,
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
-use classify_dots::DotLocationInfo;
+use classify_dots::{DetectedGrid, DotLocationInfo, DotPair, Lattice};
 use eframe::egui::mutex::RwLock;
 use log::{error, warn};
-use pathfind::Instruction;
+use pathfind::{Gesture, Stroke};
 use read_cam::{Cam, CamError, CamIndex, MyCamera, RgbaView};
+use solver::{
+    Array2D, Board, CancelToken, Heads, Progress, SolveOptions, SolveResult, SolveStatus, Stats,
+};
 
 use crate::grid_representation::GridRepresentation;
 
@@ -15,6 +23,10 @@ pub struct CameraFrameInfo {
     pub frame: Vec<u8>,
     pub frame_width: usize,
     pub frame_height: usize,
+    /// The board seen from straight above, if its corners are known
+    pub board: Vec<u8>,
+    pub board_width: usize,
+    pub board_height: usize,
 
     pub unsolved_grid: GridRepresentation,
     pub solved_grid: GridRepresentation,
@@ -24,11 +36,141 @@ pub struct CameraSettings {
     pub dot_locations: DotLocationInfo,
     pub is_actually_solved: bool,
     pub is_auto_adjusting_brightness: bool,
-    pub path: Option<Vec<Instruction>>,
+    pub path: Option<Vec<Stroke>>,
+    /// The same paths planned for dragging a stylus across a touch screen
+    pub gestures: Option<Vec<Gesture>>,
+    /// The board and its solution, for checking what the pen will do before it does it
+    pub solution: Option<(Board, Array2D)>,
+    /// How the last frame's board went, for showing why it couldn't be solved
+    pub solve_status: Option<SolveStatus>,
+    pub solve_stats: Stats,
+    /// Find the board in the next frame and put the dots on it
+    pub is_detecting_grid: bool,
+    /// Keep finding the board every so often, in case the camera or the phone moves
+    pub is_tracking_grid: bool,
+    /// Where the board was last found
+    pub detected_grid: Option<DetectedGrid>,
+    /// The dots paired up in the last frame, in the order of their colours on the board
+    pub dot_pairs: Vec<DotPair>,
+    /// Dots in the last frame that couldn't be paired with any other
+    pub unpaired_dots: Vec<[u8; 2]>,
     pub available_cams: (Vec<String>, Vec<CamIndex>),
     pub is_camera_feed: bool,
 }
 
+/// Longest a single board is worked on before giving up on it
+const SOLVE_TIME_LIMIT: Duration = Duration::from_secs(5);
+
+/// Pixels across each cell of the straightened out board
+const BOARD_CELL_PIXELS: usize = 24;
+
+/// Frames between finding the board again while tracking it
+const GRID_TRACK_FRAMES: u32 = 30;
+
+/// A board being solved on its own thread, so a hard one doesn't hold up the camera
+/// feed. Dropping it stops the solve, and the check that it has only one solution,
+/// and nothing is planned or sent back after that.
+struct BackgroundSolve {
+    cancel: CancelToken,
+    /// The most filled in the grid has been so far
+    progress: Arc<RwLock<Array2D>>,
+    result: Receiver<SolveOutcome>,
+}
+
+struct SolveOutcome {
+    result: SolveResult,
+    is_solved: bool,
+    path: Option<Vec<Stroke>>,
+    gestures: Option<Vec<Gesture>>,
+    solution: Option<(Board, Array2D)>,
+}
+
+impl BackgroundSolve {
+    fn start(board: Board, heads: Heads) -> Self {
+        let cancel = CancelToken::new();
+        let progress = Arc::new(RwLock::new(board.grid.clone()));
+        let (send, result) = channel();
+        let solve = Self {
+            cancel: cancel.clone(),
+            progress: progress.clone(),
+            result,
+        };
+
+        thread::spawn(move || {
+            let deadline = Instant::now() + SOLVE_TIME_LIMIT;
+            let options = SolveOptions {
+                deadline: Some(deadline),
+                cancel: Some(cancel.clone()),
+                progress: Some(Box::new(|update: &Progress| {
+                    *progress.write() = update.best.clone();
+                })),
+                ..Default::default()
+            };
+            let result = solver::solve_with(board.clone(), heads.clone(), options);
+            if cancel.is_cancelled() {
+                return;
+            }
+            // a properly read board has exactly one solution, anything else means some
+            // dots were missed or seen where there aren't any. Running out of time
+            // before that's known counts as not being sure it was read right.
+            let options = SolveOptions {
+                deadline: Some(deadline),
+                cancel: Some(cancel.clone()),
+                ..Default::default()
+            };
+            let is_solved = result.is_solved()
+                && solver::solutions_with(board.clone(), heads, 2, options)
+                    .is_some_and(|found| found.len() == 1);
+            if cancel.is_cancelled() {
+                return;
+            }
+            // never send the pen along something that isn't really a solution
+            let verified = is_solved
+                && solver::validate(board.clone(), &result.grid)
+                    .inspect_err(|e| error!("Solver gave a broken solution: {e}"))
+                    .is_ok();
+            let path = verified.then(|| pathfind::pathfind(board.clone(), &result.grid));
+            let gestures = verified.then(|| pathfind::swipe(board.clone(), &result.grid));
+            let solution = verified.then(|| (board, result.grid.clone()));
+
+            let _ = send.send(SolveOutcome {
+                result,
+                is_solved: verified,
+                path,
+                gestures,
+                solution,
+            });
+        });
+
+        solve
+    }
+}
+
+impl Drop for BackgroundSolve {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// The solution to the board the camera is looking at, so it's only solved once however
+/// many frames it stays in view
+struct SolveCache {
+    /// Hash of the grid, its size, the heads and how the cells are laid out, which
+    /// between them decide the solution
+    key: u64,
+    outcome: Option<SolveOutcome>,
+}
+
+impl SolveCache {
+    fn key(grid: &Array2D, heads: &Heads, lattice: Lattice) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        grid.hash(&mut hasher);
+        heads.hash(&mut hasher);
+        lattice.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
 pub enum DeviceSelect {
     Select(CamIndex),
     RefreshList,
@@ -82,16 +224,23 @@ pub fn run_process_thread(
             }
         };
 
+        let grid_size = settings.read().dot_locations.grid_size;
         let mut info = CameraFrameInfo {
             frame: vec![0; (cam.width() * cam.height() * 4) as _],
             frame_width: 0,
             frame_height: 0,
+            board: Vec::new(),
+            board_width: 0,
+            board_height: 0,
 
-            unsolved_grid: GridRepresentation::empty(),
-            solved_grid: GridRepresentation::empty(),
+            unsolved_grid: GridRepresentation::empty(grid_size.0 as _, grid_size.1 as _),
+            solved_grid: GridRepresentation::empty(grid_size.0 as _, grid_size.1 as _),
         };
 
         settings.write().is_camera_feed = true;
+        let mut cache: Option<SolveCache> = None;
+        let mut solving: Option<BackgroundSolve> = None;
+        let mut frames_since_detect = 0;
         // info!("Camera stream opened ({}x{})", cam.width(), cam.height());
 
         loop {
@@ -111,34 +260,100 @@ pub fn run_process_thread(
 
             let mut rgb = RgbaView::new(&mut info.frame, info.frame_width, info.frame_height);
 
-            let (head_locs, heads) =
-                classify_dots::get_map_layout(&settings.read().dot_locations, &mut rgb);
-
-            let (solved_grid, is_solved) = solver::solve(head_locs.clone(), heads);
-
-            if settings.read().is_auto_adjusting_brightness && !is_solved {
+            frames_since_detect += 1;
+            let should_detect = {
+                let settings = settings.read();
+                settings.is_detecting_grid
+                    || (settings.is_tracking_grid && frames_since_detect >= GRID_TRACK_FRAMES)
+            };
+            if should_detect {
+                frames_since_detect = 0;
+                let found = classify_dots::detect_grid(&rgb);
                 let mut settings = settings.write();
-                if settings.dot_locations.brightness_thresh < 20 {
-                    settings.is_auto_adjusting_brightness = false;
-                } else {
-                    settings.dot_locations.brightness_thresh -= 1;
+                settings.is_detecting_grid = false;
+                match found {
+                    Some(grid) => {
+                        settings.dot_locations.fill_detected(&grid);
+                        settings.detected_grid = Some(grid);
+                    }
+                    None => warn!("Couldn't find the board in the camera frame"),
                 }
-            } else if is_solved && settings.read().is_auto_adjusting_brightness {
+            }
+
+            let layout = classify_dots::get_map_layout(&settings.read().dot_locations, &mut rgb);
+            let heads = layout.heads();
+            let head_locs = layout.grid;
+            {
                 let mut settings = settings.write();
-                settings.is_auto_adjusting_brightness = false;
-                settings.dot_locations.brightness_thresh -= 20;
+                settings.dot_pairs = layout.pairs;
+                settings.unpaired_dots = layout.unpaired;
             }
 
-            settings.write().path = if is_solved && !solved_grid.contains_zeroes() {
-                let path = pathfind::pathfind(head_locs.clone(), &solved_grid);
-                Some(path)
-            } else {
-                None
+            // only solve again when the board changes, which also stops the old solve
+            let (lattice, topology) = {
+                let dots = &settings.read().dot_locations;
+                (dots.lattice, dots.topology())
             };
+            let key = SolveCache::key(&head_locs, &heads, lattice);
+            if cache.as_ref().is_none_or(|c| c.key != key) {
+                let board = Board::with_topology(head_locs.clone(), topology);
+                cache = Some(SolveCache { key, outcome: None });
+                solving = Some(BackgroundSolve::start(board, heads));
+                let mut settings = settings.write();
+                settings.solve_status = None;
+                settings.path = None;
+                settings.gestures = None;
+                settings.solution = None;
+            }
+            let cache = cache.as_mut().unwrap();
+
+            if let Some(outcome) = solving.as_ref().and_then(|s| s.result.try_recv().ok()) {
+                solving = None;
+                let mut settings = settings.write();
+                settings.solve_status = Some(outcome.result.status.clone());
+                settings.solve_stats = outcome.result.stats.clone();
+                settings.path = outcome.path.clone();
+                settings.gestures = outcome.gestures.clone();
+                settings.solution = outcome.solution.clone();
+                cache.outcome = Some(outcome);
+            }
 
-            info.unsolved_grid.update(&head_locs);
-            info.solved_grid.update(&solved_grid);
-            settings.read().dot_locations.draw_dots(&mut rgb);
+            if let Some(outcome) = &cache.outcome {
+                let is_solved = outcome.is_solved;
+                if settings.read().is_auto_adjusting_brightness && !is_solved {
+                    let mut settings = settings.write();
+                    if settings.dot_locations.brightness_thresh < 20 {
+                        settings.is_auto_adjusting_brightness = false;
+                    } else {
+                        settings.dot_locations.brightness_thresh -= 1;
+                    }
+                } else if is_solved && settings.read().is_auto_adjusting_brightness {
+                    let mut settings = settings.write();
+                    settings.is_auto_adjusting_brightness = false;
+                    settings.dot_locations.brightness_thresh -= 20;
+                }
+            }
+
+            info.unsolved_grid.update(&head_locs, topology);
+            match (&cache.outcome, &solving) {
+                (Some(outcome), _) => info.solved_grid.update(&outcome.result.grid, topology),
+                (None, Some(solve)) => info.solved_grid.update(&solve.progress.read(), topology),
+                (None, None) => info.solved_grid.update(&head_locs, topology),
+            }
+            // the board straightened out, before the dots are drawn over it
+            {
+                let settings = settings.read();
+                let (width, height) = settings.dot_locations.grid_size;
+                info.board_width = width as usize * BOARD_CELL_PIXELS;
+                info.board_height = height as usize * BOARD_CELL_PIXELS;
+                info.board
+                    .resize(info.board_width * info.board_height * 4, 255);
+                let mut board = RgbaView::new(&mut info.board, info.board_width, info.board_height);
+                if !settings.dot_locations.rectify(&rgb, &mut board) {
+                    (info.board_width, info.board_height) = (0, 0);
+                }
+                settings.dot_locations.draw_dots(&mut rgb);
+            }
 
             // push - pull
             push_stack.send(info).map_err(|_| ())?;
@@ -1,4 +1,4 @@
-use solver::Array2D;
+use solver::{Array2D, Cell, Topology};
 
 const COLS: [[u8; 3]; 8] = [
     [255, 0, 0],
@@ -12,28 +12,50 @@ const COLS: [[u8; 3]; 8] = [
 ];
 
 pub struct GridRepresentation {
-    pub image: [u8; 4 * 25],
+    pub image: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
 }
 
 impl GridRepresentation {
-    pub fn empty() -> Self {
-        Self { image: [0; 25 * 4] }
+    pub fn empty(width: usize, height: usize) -> Self {
+        Self {
+            image: vec![0; width * height * 4],
+            width,
+            height,
+        }
     }
-    pub fn update(&mut self, grid: &Array2D) {
-        for (i, b) in self.image.chunks_mut(4).enumerate() {
-            let x = (i % 5) as isize;
-            let y = (i / 5) as isize;
-
-            let col = grid[(x, y)];
+    /// Draws a pixel for each cell, or a block of two by two for boards with rows pushed
+    /// over, so they can be pushed over by half a cell too
+    pub fn update(&mut self, grid: &Array2D, topology: &dyn Topology) {
+        let rows = 0..grid.height() as isize;
+        let shifted = rows.clone().any(|y| topology.centre((0, y)).0 != 0.);
+        let scale = if shifted { 2 } else { 1 };
+        self.width = grid.width() * scale + shifted as usize;
+        self.height = grid.height() * scale;
+        self.image.clear();
+        self.image.resize(self.width * self.height * 4, 0);
 
-            let col = match col {
-                0 => [0, 0, 0],
-                255 => [255, 255, 255],
-                col => COLS[col as usize % COLS.len()],
-            };
-            let col = [col[0], col[1], col[2], 100];
+        for y in rows {
+            for x in 0..grid.width() as isize {
+                let col = match grid[(x, y)] {
+                    Cell::Empty | Cell::Bridge { horizontal: 0, .. } => [0, 0, 0],
+                    Cell::Wall => [255, 255, 255],
+                    Cell::Colour(col) => COLS[col as usize % COLS.len()],
+                    // only one of the paths over a bridge can be shown
+                    Cell::Bridge { horizontal, .. } => COLS[horizontal as usize % COLS.len()],
+                };
+                let col = [col[0], col[1], col[2], 100];
 
-            b.copy_from_slice(&col);
+                let left = (topology.centre((x, y)).0 * scale as f64).round() as usize;
+                let top = y as usize * scale;
+                for py in top..top + scale {
+                    let start = (py * self.width + left) * 4;
+                    for b in self.image[start..start + scale * 4].chunks_mut(4) {
+                        b.copy_from_slice(&col);
+                    }
+                }
+            }
         }
     }
 }
@@ -6,17 +6,18 @@ use std::{
     thread,
 };
 
-use classify_dots::DotLocationInfo;
+use classify_dots::{DotLocationInfo, Lattice};
 use eframe::{
     egui::{
-        mutex::RwLock, Color32, ColorImage, ComboBox, Image, Key, Sense, SidePanel, Slider,
-        TextureHandle, TextureOptions, TopBottomPanel, ViewportBuilder,
+        mutex::RwLock, vec2, Button, Color32, ColorImage, ComboBox, Image, Key, Rect, Sense, Shape,
+        SidePanel, Slider, TextureHandle, TextureOptions, TopBottomPanel, Ui, ViewportBuilder,
     },
     epaint::ImageDelta,
     App, CreationContext, NativeOptions,
 };
 use log::info;
-use pathfind::Instruction;
+use pathfind::{Simulation, Stroke};
+use solver::{Board, SolveStatus, Stats};
 
 use crate::{
     camera_input::{self, CameraFrameInfo, CameraSettings, DeviceSelect},
@@ -49,12 +50,24 @@ pub fn go() {
     let settings = Arc::new(RwLock::new(CameraSettings {
         dot_locations: DotLocationInfo {
             point_locations: vec![],
+            grid_size: (5, 5),
             dot_size: 10,
             brightness_thresh: 200,
+            lattice: Lattice::Square,
+            perspective: None,
         },
         is_actually_solved: false,
         is_auto_adjusting_brightness: false,
         path: None,
+        gestures: None,
+        solution: None,
+        solve_status: None,
+        solve_stats: Stats::default(),
+        is_detecting_grid: false,
+        is_tracking_grid: false,
+        detected_grid: None,
+        dot_pairs: Vec::new(),
+        unpaired_dots: Vec::new(),
         available_cams: (Vec::new(), Vec::new()),
         is_camera_feed: false,
     }));
@@ -101,6 +114,7 @@ pub struct MyApp {
     camera_select: Sender<DeviceSelect>,
 
     frame_texture: TextureHandle,
+    board_texture: TextureHandle,
 
     heads_texture: TextureHandle,
     solved_texture: TextureHandle,
@@ -111,6 +125,10 @@ pub struct MyApp {
     motor_state: Arc<RwLock<MotorState>>,
     motor_command: Sender<MotorCommand>,
     auto_penup: bool,
+    /// Draw strokes as smooth streams of gotos along rounded corners
+    smooth_strokes: bool,
+    /// Draw each colour as one drag of a stylus, for touch screens
+    swipe: bool,
 
     mot_tl: Option<(u32, u32)>,
     mot_tr: Option<(u32, u32)>,
@@ -150,6 +168,11 @@ impl MyApp {
                 ColorImage::new([256, 256], Color32::BLACK),
                 TextureOptions::NEAREST,
             ),
+            board_texture: cc.egui_ctx.load_texture(
+                "board_frame",
+                ColorImage::new([1, 1], Color32::BLACK),
+                TextureOptions::NEAREST,
+            ),
             heads_texture: cc.egui_ctx.load_texture(
                 "heads_frame",
                 ColorImage::new([5, 5], Color32::BLACK),
@@ -175,6 +198,8 @@ impl MyApp {
             clicked_loc: None,
             selected_camera: 0,
             auto_penup: true,
+            smooth_strokes: false,
+            swipe: false,
             rapid_solve_state: RapidSolveState::Off,
 
             motor_speed: 5000,
@@ -196,13 +221,30 @@ impl App for MyApp {
                 ImageDelta::full(cam_img, TextureOptions::NEAREST),
             );
 
-            let heads_img = ColorImage::from_rgba_premultiplied([5, 5], &data.unsolved_grid.image);
+            if data.board_width > 0 {
+                let board_img = ColorImage::from_rgba_premultiplied(
+                    [data.board_width, data.board_height],
+                    &data.board,
+                );
+                ctx.tex_manager().write().set(
+                    self.board_texture.id(),
+                    ImageDelta::full(board_img, TextureOptions::NEAREST),
+                );
+            }
+
+            let heads_img = ColorImage::from_rgba_premultiplied(
+                [data.unsolved_grid.width, data.unsolved_grid.height],
+                &data.unsolved_grid.image,
+            );
             ctx.tex_manager().write().set(
                 self.heads_texture.id(),
                 ImageDelta::full(heads_img, TextureOptions::NEAREST),
             );
 
-            let solved_img = ColorImage::from_rgba_premultiplied([5, 5], &data.solved_grid.image);
+            let solved_img = ColorImage::from_rgba_premultiplied(
+                [data.solved_grid.width, data.solved_grid.height],
+                &data.solved_grid.image,
+            );
             ctx.tex_manager().write().set(
                 self.solved_texture.id(),
                 ImageDelta::full(solved_img, TextureOptions::NEAREST),
@@ -211,8 +253,10 @@ impl App for MyApp {
             self.frame_return.send(data).unwrap();
         }
 
+        let grid_size = self.camera_settings.read().dot_locations.grid_size;
+
         TopBottomPanel::top("camera_feed_frame").show(ctx, |ui| {
-            ui.columns(3, |ui| {
+            ui.columns(4, |ui| {
                 ui[0].heading("Camera Feed");
                 // draw camera frame
                 let response = ui[0].add(
@@ -220,7 +264,7 @@ impl App for MyApp {
                         .shrink_to_fit()
                         .sense(Sense::click()),
                 );
-                if response.clicked() && self.head_y < 5 {
+                if response.clicked() && self.head_y < grid_size.1 {
                     if let Some(position) = response.interact_pointer_pos() {
                         let position = position - response.rect.left_top();
                         let click_x = position.x / response.rect.width();
@@ -240,18 +284,34 @@ impl App for MyApp {
                             .push(([click_x, click_y], [self.head_x, self.head_y]));
 
                         self.head_x += 1;
-                        if self.head_x == 5 {
+                        if self.head_x == grid_size.0 {
                             self.head_x = 0;
                             self.head_y += 1;
                         }
                     }
                 }
 
-                ui[1].heading("Flow Free Heads Detected");
-                ui[1].add(Image::from_texture(&self.heads_texture).shrink_to_fit());
+                ui[1].heading("Board Straightened");
+                if self
+                    .camera_settings
+                    .read()
+                    .dot_locations
+                    .perspective
+                    .is_some()
+                {
+                    ui[1].add(Image::from_texture(&self.board_texture).shrink_to_fit());
+                } else {
+                    ui[1].label("Place the board's corners to see it straight on");
+                }
 
-                ui[2].heading("Flow Free Solved");
-                ui[2].add(Image::from_texture(&self.solved_texture).shrink_to_fit());
+                ui[2].heading("Flow Free Heads Detected");
+                ui[2].add(Image::from_texture(&self.heads_texture).shrink_to_fit());
+
+                ui[3].heading("Flow Free Solved");
+                let response = ui[3].add(Image::from_texture(&self.solved_texture).shrink_to_fit());
+                if let Some((board, simulation)) = self.preview() {
+                    draw_preview(&ui[3], response.rect, &board, &simulation);
+                }
             });
 
             ui.add_space(40.);
@@ -317,22 +377,126 @@ impl App for MyApp {
                 }
             }
 
+            let settings = self.camera_settings.read();
+            match (&settings.solve_status, &settings.path) {
+                (None, _) => ui.label("Not solved yet"),
+                (Some(SolveStatus::Solved), None) => {
+                    ui.label("Solved, but not uniquely - check the dots")
+                }
+                (Some(status), _) => ui.label(status.to_string()),
+            };
             ui.label(format!(
-                "Is solved: {}",
-                self.camera_settings.read().path.is_some()
+                "Searched {} nodes ({} backtracks) in {:.1?}",
+                settings.solve_stats.nodes,
+                settings.solve_stats.backtracks,
+                settings.solve_stats.elapsed
             ));
+            // the pair most likely to be wrong, which is the first place to look if the
+            // board won't solve
+            let least_sure = (settings.dot_pairs.iter().zip(1..))
+                .min_by(|(a, _), (b, _)| a.confidence.total_cmp(&b.confidence));
+            if let Some((pair, colour)) = least_sure {
+                ui.label(format!(
+                    "Least sure pair is colour {colour}, {:.0}% sure",
+                    pair.confidence * 100.
+                ));
+            }
+            for [x, y] in &settings.unpaired_dots {
+                ui.colored_label(Color32::RED, format!("The dot at ({x}, {y}) has no pair"));
+            }
+            drop(settings);
 
             let result = ui.add(Slider::new(&mut dot_size, 0..=20).text("Dot size"));
             if result.changed() {
                 self.camera_settings.write().dot_locations.dot_size = dot_size;
             }
 
-            if ui.button("Clear points").clicked() {
+            let (mut grid_width, mut grid_height) = grid_size;
+            let width_changed = ui
+                .add(Slider::new(&mut grid_width, 2..=15).text("Grid width"))
+                .changed();
+            let height_changed = ui
+                .add(Slider::new(&mut grid_height, 2..=15).text("Grid height"))
+                .changed();
+
+            let mut hex = self.camera_settings.read().dot_locations.lattice == Lattice::Hex;
+            let lattice_changed = ui.checkbox(&mut hex, "Hex cells").changed();
+            if lattice_changed {
+                self.camera_settings.write().dot_locations.lattice =
+                    if hex { Lattice::Hex } else { Lattice::Square };
+            }
+
+            // the first three clicks are taken as the top left, top right and bottom left
+            // cells, and the rest of the cells are worked out from them
+            let corners = {
+                let settings = self.camera_settings.read();
+                match settings.dot_locations.point_locations.as_slice() {
+                    [tl, tr, bl] => Some((tl.0, tr.0, bl.0)),
+                    _ => None,
+                }
+            };
+            let fill = ui.add_enabled(corners.is_some(), Button::new("Fill from corners"));
+            if fill.clicked() {
+                let (tl, tr, bl) = corners.unwrap();
                 self.camera_settings
                     .write()
                     .dot_locations
-                    .point_locations
-                    .clear();
+                    .fill_lattice(tl, tr, bl);
+                self.head_x = 0;
+                self.head_y = grid_size.1;
+            }
+
+            // or four clicks on the outside corners of the board, going clockwise from the
+            // top left, which also copes with the camera looking at it from an angle
+            let board_corners = {
+                let settings = self.camera_settings.read();
+                match settings.dot_locations.point_locations.as_slice() {
+                    [tl, tr, br, bl] => Some([tl.0, tr.0, br.0, bl.0]),
+                    _ => None,
+                }
+            };
+            let fill = ui.add_enabled(
+                board_corners.is_some(),
+                Button::new("Fill from board corners"),
+            );
+            if fill.clicked()
+                && self
+                    .camera_settings
+                    .write()
+                    .dot_locations
+                    .fill_corners(board_corners.unwrap())
+            {
+                self.head_x = 0;
+                self.head_y = grid_size.1;
+            }
+
+            // or the whole board can be found in the camera frame
+            ui.horizontal(|ui| {
+                if ui.button("Find board").clicked() {
+                    self.camera_settings.write().is_detecting_grid = true;
+                }
+                let mut tracking = self.camera_settings.read().is_tracking_grid;
+                if ui.checkbox(&mut tracking, "Keep finding it").changed() {
+                    self.camera_settings.write().is_tracking_grid = tracking;
+                }
+            });
+            if let Some(grid) = self.camera_settings.read().detected_grid {
+                ui.label(format!("Found a {}x{} board", grid.size.0, grid.size.1));
+                self.head_x = 0;
+                self.head_y = grid_size.1;
+            }
+
+            if ui.button("Clear points").clicked()
+                || width_changed
+                || height_changed
+                || lattice_changed
+            {
+                let mut settings = self.camera_settings.write();
+                settings.dot_locations.grid_size = (grid_width, grid_height);
+                settings.dot_locations.point_locations.clear();
+                settings.dot_locations.perspective = None;
+                settings.detected_grid = None;
+                settings.is_tracking_grid = false;
                 self.head_x = 0;
                 self.head_y = 0;
             }
@@ -368,6 +532,9 @@ impl App for MyApp {
                     "Motor last state: {:?}",
                     self.motor_state.read().last_response
                 ));
+                if let Some((width, height)) = self.motor_state.read().calibrated_size {
+                    ui.label(format!("Calibrated for a {width}x{height} grid"));
+                }
 
                 ui.add_enabled_ui(!is_executing, |ui| {
                     ui.horizontal(|ui| {
@@ -391,6 +558,17 @@ impl App for MyApp {
                             .unwrap();
                     }
 
+                    let smooth = ui
+                        .checkbox(&mut self.smooth_strokes, "Smooth strokes")
+                        .on_hover_text("Slower to send, and needs the speed set after calibrating");
+                    if smooth.changed() {
+                        self.motor_command
+                            .send(MotorCommand::SetSmoothStrokes(self.smooth_strokes))
+                            .unwrap();
+                    }
+
+                    ui.checkbox(&mut self.swipe, "Swipe gestures");
+
                     if ui.button("Get outa my way").clicked() {
                         self.move_out_of_way();
                     }
@@ -481,8 +659,11 @@ impl App for MyApp {
                         ui.add_enabled_ui(points.is_some(), |ui| {
                             if ui.button("Calibrate").clicked() {
                                 let points = points.unwrap();
+                                let topology = self.camera_settings.read().dot_locations.topology();
                                 self.motor_command
-                                    .send(MotorCommand::Calibrate(points.0, points.1, points.2))
+                                    .send(MotorCommand::Calibrate(
+                                        points.0, points.1, points.2, grid_size, topology,
+                                    ))
                                     .unwrap();
                             }
                         });
@@ -494,9 +675,9 @@ impl App for MyApp {
         if is_fully_calibrated {
             SidePanel::right("motor_grid_controls").show(ctx, |ui| {
                 ui.add_enabled_ui(!is_executing, |ui| {
-                    for y in 0..5 {
+                    for y in 0..grid_size.1 {
                         ui.horizontal(|ui| {
-                            for x in 0..5 {
+                            for x in 0..grid_size.0 {
                                 if ui.button(format!("{x}, {y}")).clicked() {
                                     self.motor_command
                                         .send(MotorCommand::MoveToGrid(x, y))
@@ -508,26 +689,35 @@ impl App for MyApp {
 
                     if ui.button("Test sequence").clicked() {
                         let path = vec![
-                            Instruction::Goto(0, 0),
-                            Instruction::Down,
-                            Instruction::Down,
-                            Instruction::Right,
-                            Instruction::Goto(2, 2),
-                            Instruction::Up,
-                            Instruction::Left,
+                            Stroke {
+                                colour: 1,
+                                vertices: vec![(0, 0), (0, 2), (1, 2)],
+                                pen_down: true,
+                            },
+                            Stroke {
+                                colour: 2,
+                                vertices: vec![(2, 2), (2, 1), (1, 1)],
+                                pen_down: true,
+                            },
                         ];
                         self.motor_command
-                            .send(MotorCommand::MotorExecute(path))
+                            .send(MotorCommand::MotorExecute(path, None))
                             .unwrap();
                     }
 
-                    if let Some(path) = &self.camera_settings.read().path {
-                        if ui.button("ACCIO ROBOT GO OF DOOOOOM").clicked() {
+                    if let Some(path) = self.solution_strokes() {
+                        let problems = self.preview().map_or(Vec::new(), |(_, s)| s.problems);
+                        let go = Button::new("ACCIO ROBOT GO OF DOOOOOM");
+                        if ui.add_enabled(problems.is_empty(), go).clicked() {
                             info!("Sending commands: {:?}", path);
+                            let solution = self.camera_settings.read().solution.clone();
                             self.motor_command
-                                .send(MotorCommand::MotorExecute(path.clone()))
+                                .send(MotorCommand::MotorExecute(path, solution))
                                 .unwrap();
                         }
+                        for problem in problems {
+                            ui.colored_label(Color32::RED, problem.to_string());
+                        }
                     }
 
                     if !self.rapid_solve_state.is_active() {
@@ -549,6 +739,26 @@ impl App for MyApp {
 }
 
 impl MyApp {
+    /// How to draw the solution the camera can see, if there is one
+    fn solution_strokes(&self) -> Option<Vec<Stroke>> {
+        let settings = self.camera_settings.read();
+        if self.swipe {
+            let gestures = settings.gestures.as_ref()?;
+            Some(pathfind::gesture_strokes(gestures))
+        } else {
+            settings.path.clone()
+        }
+    }
+
+    /// What drawing the solution would do
+    fn preview(&self) -> Option<(Board, Simulation)> {
+        let strokes = self.solution_strokes()?;
+        let settings = self.camera_settings.read();
+        let (board, solved) = settings.solution.as_ref()?;
+        let simulation = pathfind::simulate(board.clone(), solved, &pathfind::pen_moves(&strokes));
+        Some((board.clone(), simulation))
+    }
+
     fn move_out_of_way(&mut self) {
         let size = self.motor_state.read().size;
 
@@ -583,9 +793,10 @@ impl MyApp {
                 }
             }
             RapidSolveState::WaitingForCamera => {
-                if let Some(path) = &self.camera_settings.read().path {
+                if let Some(path) = self.solution_strokes() {
+                    let solution = self.camera_settings.read().solution.clone();
                     self.motor_command
-                        .send(MotorCommand::MotorExecute(path.clone()))
+                        .send(MotorCommand::MotorExecute(path, solution))
                         .unwrap();
                     self.rapid_solve_state = RapidSolveState::WaitingForMotor;
                     info!("Waiting for motor");
@@ -600,3 +811,37 @@ impl MyApp {
         }
     }
 }
+
+/// Draws over the solution what the pen will do: white where it draws, dashed where it
+/// moves with the pen up, and a red ring anywhere something goes wrong
+fn draw_preview(ui: &Ui, rect: Rect, board: &Board, simulation: &Simulation) {
+    let topology = board.topology();
+    let (width, height) = (board.grid.width() as isize, board.grid.height() as isize);
+    // how far the cells go, in the same units as their centres
+    let across = (0..height)
+        .map(|y| topology.centre((width - 1, y)).0 as f32 + 1.)
+        .fold(1., f32::max);
+    let row = topology.centre((0, 1)).1 as f32;
+    let down = row * height as f32;
+    let centre = |pos: (isize, isize)| {
+        let (x, y) = topology.centre(pos);
+        rect.min
+            + vec2(
+                (x as f32 + 0.5) / across * rect.width(),
+                (y as f32 + row / 2.) / down * rect.height(),
+            )
+    };
+    let painter = ui.painter_at(rect);
+    for line in &simulation.lines {
+        let points = [centre(line.from), centre(line.to)];
+        if line.colour.is_some() {
+            painter.line_segment(points, (2., Color32::WHITE));
+        } else {
+            painter.extend(Shape::dashed_line(&points, (1., Color32::GRAY), 4., 4.));
+        }
+    }
+    let radius = rect.width() / across / 3.;
+    for problem in &simulation.problems {
+        painter.circle_stroke(centre(problem.at()), radius, (2., Color32::RED));
+    }
+}
@@ -4,21 +4,37 @@ use std::sync::{
 };
 
 use eframe::egui::mutex::RwLock;
-use log::warn;
-use motor_control::{Motor, MotorResponse, SolvingCommand};
-use pathfind::Instruction;
+use motor_control::{
+    trajectory::{self, GridMap, Limits},
+    AbsoluteCommand, Motor, MotorResponse, SolvingCommand,
+};
+use pathfind::{Pen, Stroke};
+use solver::{Array2D, Board, Topology};
 
 pub enum MotorCommand {
     Wakeup,
     MoveTo(u32, u32),
     MoveToGrid(u8, u8),
     Home,
-    Calibrate((u32, u32), (u32, u32), (u32, u32)),
-    MotorExecute(Vec<Instruction>),
+    /// Three corners of the grid, its size and how its cells are laid out
+    Calibrate(
+        (u32, u32),
+        (u32, u32),
+        (u32, u32),
+        (u8, u8),
+        &'static dyn Topology,
+    ),
+    /// Draws the strokes. Given the board and its solution, the commands are checked
+    /// against it first and aren't run if they'd draw anything wrong.
+    MotorExecute(Vec<Stroke>, Option<(Board, Array2D)>),
     PenUp,
     PenDown,
     SetAutoPenup(bool),
     SetSpeed(i32, i32),
+    /// Whether to draw strokes as smooth streams of gotos along rounded corners rather
+    /// than a grid move to each corner. Each goto is sent on its own, which is slower
+    /// to send than an instruction chain, so this is off until asked for.
+    SetSmoothStrokes(bool),
 }
 
 pub struct MotorState {
@@ -29,6 +45,8 @@ pub struct MotorState {
 
     pub has_homed: bool,
     pub has_calibrated: bool,
+    /// Cells across and down the grid the firmware was last calibrated for
+    pub calibrated_size: Option<(u8, u8)>,
 
     pub size: (u16, u16),
 }
@@ -42,6 +60,7 @@ impl MotorState {
             last_response: MotorResponse::Ok,
             has_homed: false,
             has_calibrated: false,
+            calibrated_size: None,
             size: (0, 0),
         }
     }
@@ -55,6 +74,7 @@ pub fn run_motor_thread(
     commands: Receiver<MotorCommand>,
     state: Arc<RwLock<MotorState>>,
 ) -> Result<(), RecvError> {
+    let mut smooth = false;
     loop {
         let mut motor = match Motor::new("/dev/ttyACM0") {
             Ok(motor) => motor,
@@ -75,9 +95,15 @@ pub fn run_motor_thread(
         state_w.connect_active = true;
         state_w.is_executing = false;
         state_w.has_calibrated = false;
+        state_w.calibrated_size = None;
         state_w.has_homed = false;
         drop(state_w);
 
+        // what the firmware was last told, to plan smooth strokes with. Until both are
+        // known, or if smooth strokes are off, strokes are drawn one grid move at a time.
+        let mut grid: Option<GridMap> = None;
+        let mut limits: Option<Limits> = None;
+
         loop {
             let command = commands.recv()?;
             state.write().is_executing = true;
@@ -102,20 +128,53 @@ pub fn run_motor_thread(
                     }
                     Err(e) => Err(e),
                 },
-                MotorCommand::Calibrate(tl, tr, bl) => motor.calibrate_3point(
+                MotorCommand::Calibrate(tl, tr, bl, grid_size, topology) => motor.calibrate_3point(
                     (tl.0 as _, tl.1 as _),
                     (tr.0 as _, tr.1 as _),
                     (bl.0 as _, bl.1 as _),
+                    *grid_size,
+                    *topology,
                 ),
-                MotorCommand::MotorExecute(cmds) => {
-                    let new_commands = convert_commands(cmds);
+                MotorCommand::MotorExecute(cmds, solution) => {
+                    let smoothed = match (&grid, &limits) {
+                        (Some(grid), Some(limits)) if smooth => {
+                            let lines: Vec<_> = (cmds.iter().filter(|s| s.pen_down))
+                                .map(|s| s.vertices.clone())
+                                .collect();
+                            Some((trajectory::draw(&lines, grid, limits), grid))
+                        }
+                        _ => None,
+                    };
+                    let chains = motor_control::split_chains(&convert_commands(cmds));
 
-                    motor.execute_in_order(&new_commands)
+                    let problems = solution.as_ref().map_or(Vec::new(), |(board, solved)| {
+                        let moves = match &smoothed {
+                            Some((commands, grid)) => absolute_pen_moves(commands, grid),
+                            None => pen_moves(&chains.concat()),
+                        };
+                        pathfind::simulate(board.clone(), solved, &moves).problems
+                    });
+                    if problems.is_empty() {
+                        match &smoothed {
+                            Some((commands, _)) => motor.execute_absolute(commands),
+                            None => motor.execute_chains(&chains),
+                        }
+                    } else {
+                        println!("Not drawing, the commands would go wrong:");
+                        for problem in problems {
+                            println!("  {problem}");
+                        }
+                        Ok(MotorResponse::Ok)
+                    }
                 }
                 MotorCommand::PenUp => motor.pen_up(),
                 MotorCommand::PenDown => motor.pen_down(),
                 MotorCommand::SetAutoPenup(auto_penup) => motor.set_auto_pen_up(*auto_penup),
                 MotorCommand::SetSpeed(speed, accel) => motor.set_motor_speed(*speed, *accel),
+                MotorCommand::SetSmoothStrokes(on) => {
+                    smooth = *on;
+                    Ok(MotorResponse::Ok)
+                }
             };
 
             match result {
@@ -129,13 +188,28 @@ pub fn run_motor_thread(
                             state.size = new_size;
                         }
                         match command {
-                            MotorCommand::Calibrate(_, _, _) => state.has_calibrated = true,
+                            MotorCommand::Calibrate(tl, tr, bl, grid_size, topology) => {
+                                state.has_calibrated = true;
+                                state.calibrated_size = Some(grid_size);
+                                grid = Some(GridMap::from_corners(
+                                    (tl.0 as _, tl.1 as _),
+                                    (tr.0 as _, tr.1 as _),
+                                    (bl.0 as _, bl.1 as _),
+                                    grid_size,
+                                    topology,
+                                ));
+                            }
+                            MotorCommand::SetSpeed(speed, accel) => {
+                                limits = Some(Limits::new(speed, accel))
+                            }
                             MotorCommand::Home => state.has_homed = true,
                             _ => (),
                         }
                     } else if response == MotorResponse::Reset {
                         state.has_homed = false;
                         state.has_calibrated = false;
+                        state.calibrated_size = None;
+                        (grid, limits) = (None, None);
                         match motor.send_send_ack() {
                             Ok(_) => (),
                             Err(_) => break,
@@ -152,37 +226,41 @@ pub fn run_motor_thread(
     }
 }
 
-pub fn convert_commands(cmds: &Vec<Instruction>) -> Vec<SolvingCommand> {
-    let mut current_loc = (0, 0);
-    let mut prev_instr = None;
+pub fn convert_commands(strokes: &[Stroke]) -> Vec<SolvingCommand> {
+    pathfind::pen_moves(strokes)
+        .into_iter()
+        .map(|pen| match pen {
+            Pen::Up => SolvingCommand::PenUp,
+            Pen::Down => SolvingCommand::PenDown,
+            Pen::Goto((x, y)) => SolvingCommand::Goto(x as u8, y as u8),
+        })
+        .collect()
+}
 
-    let mut new_commands = vec![];
+/// What the commands tell the pen to do, for checking them with [`pathfind::simulate`]
+pub fn pen_moves(commands: &[SolvingCommand]) -> Vec<Pen> {
+    commands
+        .iter()
+        .map(|command| match *command {
+            SolvingCommand::Goto(x, y) => Pen::Goto((x as isize, y as isize)),
+            SolvingCommand::PenUp => Pen::Up,
+            SolvingCommand::PenDown => Pen::Down,
+        })
+        .collect()
+}
 
-    for cmd in cmds {
-        if let Some(prev_instr) = &prev_instr {
-            if *prev_instr != *cmd {
-                if let Instruction::Goto(_, _) = prev_instr {
-                    new_commands.push(SolvingCommand::PenUp);
-                }
-                new_commands.push(SolvingCommand::Goto(current_loc.0, current_loc.1));
-                if let Instruction::Goto(_, _) = prev_instr {
-                    new_commands.push(SolvingCommand::PenDown);
-                }
+/// Like [`pen_moves`] for commands with positions in motor steps, which are taken to
+/// the nearest cell of `grid`
+pub fn absolute_pen_moves(commands: &[AbsoluteCommand], grid: &GridMap) -> Vec<Pen> {
+    commands
+        .iter()
+        .map(|command| match *command {
+            AbsoluteCommand::Goto(x, y) => {
+                let (x, y) = grid.nearest_cell((x, y));
+                Pen::Goto((x as isize, y as isize))
             }
-        }
-
-        match cmd {
-            Instruction::Up => current_loc.1 -= 1,
-            Instruction::Down => current_loc.1 += 1,
-            Instruction::Left => current_loc.0 -= 1,
-            Instruction::Right => current_loc.0 += 1,
-            Instruction::Goto(x, y) => current_loc = (*x, *y),
-            instr => warn!("Not yet implemented: {:?}", instr),
-        };
-
-        prev_instr = Some(*cmd);
-    }
-    new_commands.push(SolvingCommand::Goto(current_loc.0, current_loc.1));
-    new_commands.push(SolvingCommand::PenUp);
-    new_commands
+            AbsoluteCommand::PenUp => Pen::Up,
+            AbsoluteCommand::PenDown => Pen::Down,
+        })
+        .collect()
 }
@@ -0,0 +1,229 @@
+//! Finding the board in a camera frame, so the cells don't have to be clicked on by hand.
+//!
+//! The board is a grid of thin lines on a dark background inside a border, so adding up
+//! how much brighter each pixel is than the ones either side of it, down every column
+//! and along every row, gives peaks where the lines are. The outermost strong peaks are
+//! the border, and the number of cells is the most that still puts every line inside it
+//! on a peak.
+
+use read_cam::Image;
+
+/// Where the board is in a frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DetectedGrid {
+    /// Pixel positions of the left, top, right and bottom of the border
+    pub bounds: [f32; 4],
+    /// Cells across and down
+    pub size: (u8, u8),
+}
+
+impl DetectedGrid {
+    /// Where the middle of a cell is on screen
+    pub fn centre(&self, x: u8, y: u8) -> [u32; 2] {
+        let [left, top, right, bottom] = self.bounds;
+        let across = (right - left) / self.size.0 as f32;
+        let down = (bottom - top) / self.size.1 as f32;
+        [
+            (left + (x as f32 + 0.5) * across).round().max(0.) as u32,
+            (top + (y as f32 + 0.5) * down).round().max(0.) as u32,
+        ]
+    }
+}
+
+/// Fewest and most cells a side of the board can have
+const SIZES: std::ops::RangeInclusive<u8> = 2..=15;
+
+/// How far either side of a pixel its background is taken from, wide enough to get past a
+/// slightly blurry line
+const LINE_WIDTH: usize = 3;
+
+/// How strong a peak has to be, compared to the strongest, to be taken as the border
+const BORDER_STRENGTH: f32 = 0.3;
+
+/// How strong the weakest line inside the board has to be, compared to the weakest line
+/// of the best fitting count, to count as a line
+const LINE_STRENGTH: f32 = 0.5;
+
+/// Cells narrower than this many pixels are too small to be what the camera is seeing
+const MIN_CELL: f32 = 4.;
+
+/// Finds the board's border and how many cells it has. Only works for square cells, on
+/// a board with its edges lined up with the sides of the frame, since the lines are only
+/// looked for straight down and straight across. A tilted board or one seen at an angle
+/// has to have its corners placed by hand.
+pub fn detect_grid(img: &impl Image) -> Option<DetectedGrid> {
+    let (width, height) = (img.width(), img.height());
+    if width <= 2 * LINE_WIDTH || height <= 2 * LINE_WIDTH {
+        return None;
+    }
+    let brightness: Vec<f32> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let [r, g, b] = img.read_pixel(x as u32, y as u32);
+            r as f32 + g as f32 + b as f32
+        })
+        .collect();
+    let at = |x: usize, y: usize| brightness[y * width + x];
+
+    // first over the whole frame, then again over only the board so that nothing
+    // outside it gets counted
+    let mut bounds = [0, 0, width - 1, height - 1];
+    let mut grid = None;
+    for _ in 0..2 {
+        let [left, top, right, bottom] = bounds;
+        // how much brighter a pixel is than the ones `LINE_WIDTH` either side of it
+        let line = |x: usize, y: usize, [dx, dy]: [usize; 2]| {
+            (at(x, y) - (at(x - dx, y - dy) + at(x + dx, y + dy)) / 2.).max(0.)
+        };
+        let inner = |len: usize| LINE_WIDTH..len - LINE_WIDTH;
+        let columns: Vec<f32> = (0..width)
+            .map(|x| {
+                if !inner(width).contains(&x) {
+                    return 0.;
+                }
+                inner(height)
+                    .filter(|y| (top..=bottom).contains(y))
+                    .map(|y| line(x, y, [LINE_WIDTH, 0]))
+                    .sum()
+            })
+            .collect();
+        let rows: Vec<f32> = (0..height)
+            .map(|y| {
+                if !inner(height).contains(&y) {
+                    return 0.;
+                }
+                inner(width)
+                    .filter(|x| (left..=right).contains(x))
+                    .map(|x| line(x, y, [0, LINE_WIDTH]))
+                    .sum()
+            })
+            .collect();
+
+        let (left, right, across) = fit(&columns)?;
+        let (top, bottom, down) = fit(&rows)?;
+        bounds = [left, top, right, bottom].map(|p| p.round() as usize);
+        grid = Some(DetectedGrid {
+            bounds: [left, top, right, bottom],
+            size: (across, down),
+        });
+    }
+    grid
+}
+
+/// Strongest value within a pixel of `pos`
+fn peak(profile: &[f32], pos: f32) -> f32 {
+    let pos = pos.round() as usize;
+    profile[pos.saturating_sub(1)..=(pos + 1).min(profile.len() - 1)]
+        .iter()
+        .copied()
+        .fold(0., f32::max)
+}
+
+/// Finds the border and the number of cells between it along one side of the board
+fn fit(profile: &[f32]) -> Option<(f32, f32, u8)> {
+    let strongest = profile.iter().copied().fold(0., f32::max);
+    if strongest <= 0. {
+        return None;
+    }
+    let strong = |p: &f32| *p >= strongest * BORDER_STRENGTH;
+    // the first and last strong pixels are on the near side of each border line
+    let first = profile.iter().position(strong)?;
+    let last = profile.iter().rposition(strong)?;
+    let line = |range: std::ops::Range<usize>| {
+        range
+            .filter(|i| *i < profile.len())
+            .max_by(|a, b| profile[*a].total_cmp(&profile[*b]))
+    };
+    let start = line(first..first + LINE_WIDTH)? as f32;
+    let end = line(last.saturating_sub(LINE_WIDTH - 1)..last + 1)? as f32;
+
+    // every line inside the border should be strong. Any count that divides the real
+    // one only looks for some of the lines and finds them all too, so it's the most
+    // cells that does that.
+    let weakest_line = |cells: u8| {
+        let spacing = (end - start) / cells as f32;
+        (1..cells)
+            .map(|i| peak(profile, start + i as f32 * spacing))
+            .fold(f32::INFINITY, f32::min)
+    };
+    let counts: Vec<(u8, f32)> = SIZES
+        .filter(|cells| (end - start) / *cells as f32 >= MIN_CELL)
+        .map(|cells| (cells, weakest_line(cells)))
+        .collect();
+    let best = counts.iter().map(|(_, s)| *s).fold(0., f32::max);
+    let (cells, _) = counts
+        .into_iter()
+        .rev()
+        .find(|(_, strength)| *strength >= best * LINE_STRENGTH)?;
+    Some((start, end, cells))
+}
+
+#[test]
+fn test_detect_grid() {
+    use crate::{draw_board, DotLocationInfo, Lattice};
+    use read_cam::RgbaView;
+    use solver::{Array2D, Cell, IndexTy};
+
+    let (width, height) = (320, 240);
+    // a board from (40, 30) with 20 pixel cells, with its lines drawn over the dots
+    let frame = |size: (u8, u8)| {
+        let mut info = DotLocationInfo {
+            point_locations: Vec::new(),
+            grid_size: size,
+            dot_size: 5,
+            brightness_thresh: 100,
+            lattice: Lattice::Square,
+            perspective: None,
+        };
+        let [right, bottom] = [size.0 as u32, size.1 as u32].map(|n| n * 20 - 20);
+        info.fill_lattice([50, 40], [50 + right, 40], [50, 40 + bottom]);
+        let mut grid = Array2D::new(size.0 as _, size.1 as _);
+        for x in 0..size.0 {
+            grid[(x as _, 0)] = Cell::Colour(x + 1);
+            grid[(x as _, size.1 as IndexTy - 1)] = Cell::Colour(x + 1);
+        }
+
+        let mut buf = vec![0; width * height * 4];
+        let mut img = RgbaView::new(&mut buf, width, height);
+        draw_board(&info, &grid, &mut img);
+        let [across, down] = [size.0 as u32, size.1 as u32].map(|n| n * 20);
+        for x in (0..=across).step_by(20) {
+            for y in 0..=down {
+                img.write_pixel(40 + x, 30 + y, [90, 90, 90]);
+            }
+        }
+        for y in (0..=down).step_by(20) {
+            for x in 0..=across {
+                img.write_pixel(40 + x, 30 + y, [90, 90, 90]);
+            }
+        }
+        (info, buf)
+    };
+
+    let (mut info, mut buf) = frame((6, 6));
+    let found = detect_grid(&RgbaView::new(&mut buf, width, height)).unwrap();
+    assert_eq!(found.size, (6, 6));
+    assert_eq!(found.bounds, [40., 30., 160., 150.]);
+    assert_eq!(found.centre(0, 0), [50, 40]);
+    assert_eq!(found.centre(5, 2), [150, 80]);
+    // the dots land where draw_board put them
+    let drawn = info.point_locations.clone();
+    info.fill_detected(&found);
+    assert_eq!(info.point_locations, drawn);
+
+    // a board of another size than the one the dots were last placed for, and not
+    // square
+    let (drawn, mut buf) = frame((12, 9));
+    let found = detect_grid(&RgbaView::new(&mut buf, width, height)).unwrap();
+    assert_eq!(found.size, (12, 9));
+    assert_eq!(found.centre(11, 8), [270, 200]);
+    info.fill_detected(&found);
+    assert_eq!(info.grid_size, (12, 9));
+    assert_eq!(info.point_locations, drawn.point_locations);
+
+    // nothing at all, and nothing but an even glow, aren't a board
+    let mut buf = vec![0; width * height * 4];
+    assert_eq!(detect_grid(&RgbaView::new(&mut buf, width, height)), None);
+    let mut buf = vec![120; width * height * 4];
+    assert_eq!(detect_grid(&RgbaView::new(&mut buf, width, height)), None);
+}
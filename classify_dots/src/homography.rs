@@ -0,0 +1,105 @@
+//! Undoing the perspective of a camera looking at the board at an angle. Straight lines
+//! stay straight from any angle, so where the four corners of the board end up on
+//! screen is enough to work out where every other point on it does.
+
+/// Maps points on one plane to where they're seen on another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Homography(pub [[f64; 3]; 3]);
+
+impl Homography {
+    /// The homography taking each point in `from` to the one at the same index in `to`.
+    /// Both have to go round the corners of a shape with no dents or twists in it.
+    pub fn from_points(from: [[f64; 2]; 4], to: [[f64; 2]; 4]) -> Option<Self> {
+        if !is_convex(from) || !is_convex(to) {
+            return None;
+        }
+        // with the bottom right fixed at 1 there are eight unknowns, and each pair of
+        // points gives two equations for them
+        let mut rows = [[0.; 9]; 8];
+        for (i, ([x, y], [u, v])) in from.into_iter().zip(to).enumerate() {
+            rows[2 * i] = [x, y, 1., 0., 0., 0., -u * x, -u * y, u];
+            rows[2 * i + 1] = [0., 0., 0., x, y, 1., -v * x, -v * y, v];
+        }
+        let h = solve(rows)?;
+        Some(Self([
+            [h[0], h[1], h[2]],
+            [h[3], h[4], h[5]],
+            [h[6], h[7], 1.],
+        ]))
+    }
+
+    /// Where a point ends up
+    pub fn map(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let [a, b, c] = self.0.map(|row| row[0] * x + row[1] * y + row[2]);
+        [a / c, b / c]
+    }
+}
+
+/// Whether every corner turns the same way, and not by nothing
+fn is_convex(corners: [[f64; 2]; 4]) -> bool {
+    let turns = [0, 1, 2, 3].map(|i| {
+        let [a, b, c] = [i, i + 1, i + 2].map(|j| corners[j % 4]);
+        (b[0] - a[0]) * (c[1] - b[1]) - (b[1] - a[1]) * (c[0] - b[0])
+    });
+    turns.iter().all(|t| *t > 0.) || turns.iter().all(|t| *t < 0.)
+}
+
+/// Solves eight equations in eight unknowns, each row ending with what it adds up to
+fn solve(mut rows: [[f64; 9]; 8]) -> Option<[f64; 8]> {
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|a, b| rows[*a][col].abs().total_cmp(&rows[*b][col].abs()))?;
+        if rows[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        rows.swap(col, pivot);
+        let pivot = rows[col];
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col] / pivot[col];
+                for (value, p) in row.iter_mut().zip(pivot).skip(col) {
+                    *value -= factor * p;
+                }
+            }
+        }
+    }
+    Some(std::array::from_fn(|i| rows[i][8] / rows[i][i]))
+}
+
+#[test]
+fn test_homography() {
+    let close = |a: [f64; 2], b: [f64; 2]| (a[0] - b[0]).abs() < 1e-6 && (a[1] - b[1]).abs() < 1e-6;
+
+    // a 4 by 3 board seen from below and to the left
+    let board = [[0., 0.], [4., 0.], [4., 3.], [0., 3.]];
+    let screen = [[100., 80.], [420., 120.], [400., 330.], [90., 400.]];
+    let forward = Homography::from_points(board, screen).unwrap();
+    let back = Homography::from_points(screen, board).unwrap();
+    for (b, s) in board.into_iter().zip(screen) {
+        assert!(close(forward.map(b), s));
+        assert!(close(back.map(s), b));
+    }
+    for point in [[0.5, 0.5], [2., 1.5], [3.5, 2.5], [1., 2.75]] {
+        assert!(close(back.map(forward.map(point)), point));
+    }
+    // the middle of the board is where its diagonals cross, not halfway between the
+    // corners
+    let [x, y] = forward.map([2., 1.5]);
+    let diagonal = |[a, c]: [[f64; 2]; 2]| (c[1] - a[1]) * (x - a[0]) - (c[0] - a[0]) * (y - a[1]);
+    assert!(diagonal([screen[0], screen[2]]).abs() < 1e-6);
+    assert!(diagonal([screen[1], screen[3]]).abs() < 1e-6);
+    assert!(!close([x, y], [252.5, 232.5]));
+
+    // seen straight on it only scales and moves
+    let straight = Homography::from_points(board, [[10., 20.], [50., 20.], [50., 50.], [10., 50.]]);
+    assert!(close(straight.unwrap().map([2., 1.]), [30., 30.]));
+
+    // corners in a line, or with two the same, aren't a board
+    let line = [[0., 0.], [1., 1.], [2., 2.], [3., 3.]];
+    assert_eq!(Homography::from_points(board, line), None);
+    assert_eq!(Homography::from_points(line, screen), None);
+    let repeated = [[0., 0.], [4., 0.], [4., 0.], [0., 3.]];
+    assert_eq!(Homography::from_points(board, repeated), None);
+    // and neither is one twisted into a bow tie
+    let twisted = [[0., 0.], [4., 0.], [0., 3.], [4., 3.]];
+    assert_eq!(Homography::from_points(board, twisted), None);
+}
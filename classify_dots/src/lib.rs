@@ -1,99 +1,368 @@
 use read_cam::Image;
-use solver::Array2D;
+use solver::{Array2D, Cell, Heads, Hex, IndexTy, Square, Topology};
+
+mod detect;
+mod homography;
+mod pairing;
+
+pub use detect::{detect_grid, DetectedGrid};
+pub use homography::Homography;
+
+/// How the cells of the board are laid out on screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Lattice {
+    #[default]
+    Square,
+    /// Rows of hexagons, with every odd row half a cell to the right
+    Hex,
+}
 
 pub struct DotLocationInfo {
     /// Maps between coordinates on screen to coordinates on grid
     pub point_locations: Vec<([u32; 2], [u8; 2])>,
+    /// Size of the board in cells (width, height)
+    pub grid_size: (u8, u8),
     pub dot_size: u32,
     pub brightness_thresh: u32,
+    pub lattice: Lattice,
+    /// Where points on the grid are on screen, in cells from the top left corner of the
+    /// board, if it's been found from its corners. Without it the board is taken to be
+    /// seen straight on.
+    pub perspective: Option<Homography>,
 }
 
 impl DotLocationInfo {
+    /// How the solver should connect the cells
+    pub fn topology(&self) -> &'static dyn Topology {
+        match self.lattice {
+            Lattice::Square => &Square,
+            Lattice::Hex => &Hex,
+        }
+    }
+
+    /// How far across a row is pushed, in cells
+    fn shift(&self, y: u8) -> f32 {
+        match self.lattice {
+            Lattice::Hex if y % 2 == 1 => 0.5,
+            _ => 0.,
+        }
+    }
+
+    /// How many cells wide and high the board is, counting the half cell the shifted
+    /// rows of a hex board stick out by
+    fn extent(&self) -> [f64; 2] {
+        let (width, height) = self.grid_size;
+        let shifted = (0..height).map(|y| self.shift(y)).fold(0., f32::max);
+        [width as f64 + shifted as f64, height as f64]
+    }
+
+    /// Where the middle of a cell is, in cells from the top left corner of the board
+    fn cell_centre(&self, [x, y]: [u8; 2]) -> [f64; 2] {
+        [x as f64 + 0.5 + self.shift(y) as f64, y as f64 + 0.5]
+    }
+
+    /// Places a dot on every cell, given where the centres of the top left, top right
+    /// and bottom left cells are on screen
+    pub fn fill_lattice(&mut self, top_left: [u32; 2], top_right: [u32; 2], bottom_left: [u32; 2]) {
+        let (width, height) = self.grid_size;
+        let last_row = height.saturating_sub(1);
+        let column = [0, 1]
+            .map(|i| (top_right[i] as f32 - top_left[i] as f32) / (width as f32 - 1.).max(1.));
+        // the bottom left cell is pushed over too if it's on an odd row
+        let row = [0, 1].map(|i| {
+            (bottom_left[i] as f32 - top_left[i] as f32 - column[i] * self.shift(last_row))
+                / (last_row as f32).max(1.)
+        });
+
+        self.point_locations.clear();
+        for y in 0..height {
+            for x in 0..width {
+                let across = x as f32 + self.shift(y);
+                let screen =
+                    [0, 1].map(|i| top_left[i] as f32 + column[i] * across + row[i] * y as f32);
+                self.point_locations
+                    .push((screen.map(|p| p.round().max(0.) as u32), [x, y]));
+            }
+        }
+        self.perspective = None;
+    }
+
+    /// Places a dot on every cell, given where the outside corners of the board are on
+    /// screen, clockwise from the top left. Unlike [`Self::fill_lattice`] this works
+    /// with the camera at an angle. Returns whether the corners make a board shape.
+    pub fn fill_corners(&mut self, corners: [[u32; 2]; 4]) -> bool {
+        let [width, height] = self.extent();
+        let board = [[0., 0.], [width, 0.], [width, height], [0., height]];
+        let Some(perspective) =
+            Homography::from_points(board, corners.map(|c| c.map(|p| p as f64)))
+        else {
+            return false;
+        };
+
+        let (width, height) = self.grid_size;
+        self.point_locations.clear();
+        for y in 0..height {
+            for x in 0..width {
+                let screen = perspective.map(self.cell_centre([x, y]));
+                self.point_locations
+                    .push((screen.map(|p| p.round().max(0.) as u32), [x, y]));
+            }
+        }
+        self.perspective = Some(perspective);
+        true
+    }
+
+    /// Places a dot on every cell of a board found by [`detect_grid`], which is always
+    /// a square one
+    pub fn fill_detected(&mut self, grid: &DetectedGrid) {
+        let [left, top, right, bottom] = grid.bounds.map(|p| p.round().max(0.) as u32);
+        self.grid_size = grid.size;
+        self.lattice = Lattice::Square;
+        self.fill_corners([[left, top], [right, top], [right, bottom], [left, bottom]]);
+    }
+
     pub fn draw_dots(&self, img: &mut impl Image) {
         for (point, _) in self.point_locations.iter() {
             img.draw_rect(self.dot_size, point[0], point[1], [255, 255, 255]);
         }
+        // and the edge of the board, to show how well the corners were placed
+        let Some(perspective) = self.perspective else {
+            return;
+        };
+        let [width, height] = self.extent();
+        let corners = [
+            [0., 0.],
+            [width, 0.],
+            [width, height],
+            [0., height],
+            [0., 0.],
+        ];
+        for edge in corners.windows(2) {
+            for i in 0..=EDGE_STEPS {
+                let t = i as f64 / EDGE_STEPS as f64;
+                let along = [0, 1].map(|k| edge[0][k] + (edge[1][k] - edge[0][k]) * t);
+                let [x, y] = perspective.map(along);
+                if x >= 0. && y >= 0. {
+                    img.write_pixel(x as u32, y as u32, [0, 255, 0]);
+                }
+            }
+        }
+    }
+
+    /// Draws the board as if the camera were straight above it, stretched to fill `out`.
+    /// Returns false without drawing anything if the corners of the board aren't known.
+    pub fn rectify(&self, img: &impl Image, out: &mut impl Image) -> bool {
+        let Some(perspective) = self.perspective else {
+            return false;
+        };
+        let [width, height] = self.extent();
+        let scale = [width / out.width() as f64, height / out.height() as f64];
+        for y in 0..out.height() as u32 {
+            for x in 0..out.width() as u32 {
+                let on_board = [(x as f64 + 0.5) * scale[0], (y as f64 + 0.5) * scale[1]];
+                let [u, v] = perspective.map(on_board);
+                let colour = if u >= 0. && v >= 0. {
+                    img.read_pixel(u as u32, v as u32)
+                } else {
+                    [0, 0, 0]
+                };
+                out.write_pixel(x, y, colour);
+            }
+        }
+        true
+    }
+
+    /// The average colour of the square `dot_size` either side of a cell's dot. With the
+    /// board's corners known the square is taken on the board rather than on screen, so
+    /// it covers the same part of every cell however far away it is.
+    fn dot_colour(&self, img: &impl Image, (screen, cell): &([u32; 2], [u8; 2])) -> [u8; 3] {
+        let size = self.dot_size;
+        let mut col = [0; 3];
+        let mut add = |px: [u8; 3]| {
+            for (c, p) in col.iter_mut().zip(px) {
+                *c += p as u32;
+            }
+        };
+        match self.perspective {
+            Some(perspective) => {
+                let [cx, cy] = self.cell_centre(*cell);
+                // pixels per cell around this one
+                let at = perspective.map([cx, cy]);
+                let along = |d: [f64; 2]| {
+                    let [x, y] = perspective.map([cx + d[0], cy + d[1]]);
+                    (x - at[0]).hypot(y - at[1])
+                };
+                let step = 2. / (along([1., 0.]) + along([0., 1.])).max(1.);
+                for i in 0..2 * size {
+                    for j in 0..2 * size {
+                        let offset = |k: u32| (k as f64 - size as f64 + 0.5) * step;
+                        let [x, y] = perspective.map([cx + offset(i), cy + offset(j)]);
+                        add(img.read_pixel(x.max(0.) as u32, y.max(0.) as u32));
+                    }
+                }
+            }
+            None => {
+                for x in (screen[0] - size)..(screen[0] + size) {
+                    for y in (screen[1] - size)..(screen[1] + size) {
+                        add(img.read_pixel(x, y));
+                    }
+                }
+            }
+        }
+        col.map(|c| (c / (size * size * 4)) as u8)
     }
 }
 
-fn pythag(a: [u8; 3], b: [u8; 3]) -> i32 {
-    (a[0] as i32 - b[0] as i32).pow(2)
-        + (a[1] as i32 - b[1] as i32).pow(2)
-        + (a[2] as i32 - b[2] as i32).pow(2)
+/// Points drawn along each edge of the board
+const EDGE_STEPS: usize = 200;
+
+/// Colours [`draw_board`] gives each path, roughly the ones the game uses. They're all
+/// bright enough to get past the usual brightness threshold.
+const PALETTE: [[u8; 3]; 16] = [
+    [255, 0, 0],
+    [0, 200, 0],
+    [0, 0, 255],
+    [238, 238, 0],
+    [255, 127, 0],
+    [0, 255, 255],
+    [255, 0, 255],
+    [165, 42, 42],
+    [128, 0, 128],
+    [255, 255, 255],
+    [160, 160, 160],
+    [128, 255, 0],
+    [245, 222, 179],
+    [60, 60, 200],
+    [0, 128, 128],
+    [255, 150, 200],
+];
+
+/// Draws the board as the camera would see it, a dot on every coloured cell, so dots
+/// can be classified without a phone in front of the camera
+pub fn draw_board(info: &DotLocationInfo, grid: &Array2D, img: &mut impl Image) {
+    for x in 0..img.width() as u32 {
+        for y in 0..img.height() as u32 {
+            img.write_pixel(x, y, [0, 0, 0]);
+        }
+    }
+    for (point, cell) in info.point_locations.iter() {
+        if let Some(Cell::Colour(colour)) = grid.get((cell[0] as _, cell[1] as _)) {
+            let col = PALETTE[(*colour as usize).saturating_sub(1) % PALETTE.len()];
+            img.draw_rect(info.dot_size, point[0], point[1], col);
+        }
+    }
 }
 
 fn brightness(x: [u8; 3]) -> u32 {
     x[0] as u32 + x[1] as u32 + x[2] as u32
 }
 
-pub fn get_map_layout(
-    info: &DotLocationInfo,
-    img: &impl read_cam::Image,
-) -> (Array2D, Vec<((isize, isize), (isize, isize))>) {
-    let mut rgbs: Vec<_> = info
+/// Two dots taken to be the ends of the same path
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DotPair {
+    pub ends: ([u8; 2], [u8; 2]),
+    /// How sure it is that they're the same colour, from 0 when there's another dot as
+    /// close in colour to one of them as they are to each other, up to 1 when there's
+    /// nothing else like them
+    pub confidence: f32,
+}
+
+/// The dots the camera can see on the board
+pub struct MapLayout {
+    /// Each pair of dots in a colour of its own, and so is each dot that couldn't be
+    /// paired, which the solver reports as malformed
+    pub grid: Array2D,
+    pub pairs: Vec<DotPair>,
+    pub unpaired: Vec<[u8; 2]>,
+}
+
+impl MapLayout {
+    /// The ends of each path, for the solver
+    pub fn heads(&self) -> Heads {
+        let pos = |[x, y]: [u8; 2]| (x as IndexTy, y as IndexTy);
+        self.pairs
+            .iter()
+            .map(|pair| (pos(pair.ends.0), pos(pair.ends.1)))
+            .collect()
+    }
+}
+
+pub fn get_map_layout(info: &DotLocationInfo, img: &impl read_cam::Image) -> MapLayout {
+    let (cells, colours): (Vec<[u8; 2]>, Vec<[u8; 3]>) = info
         .point_locations
         .iter()
-        .enumerate()
-        .map(|(i, (img_coord, _))| {
-            let size = info.dot_size;
-            let mut col = [0; 3];
-            for x in (img_coord[0] - size)..(img_coord[0] + size) {
-                for y in (img_coord[1] - size)..(img_coord[1] + size) {
-                    let px = img.read_pixel(x, y);
-
-                    col[0] += px[0] as u32;
-                    col[1] += px[1] as u32;
-                    col[2] += px[2] as u32;
-                }
-            }
-            col[0] /= size * size * 4;
-            col[1] /= size * size * 4;
-            col[2] /= size * size * 4;
-
-            let col = [col[0] as u8, col[1] as u8, col[2] as u8];
+        .map(|point| {
+            let col = info.dot_colour(img, point);
             // println!("bright: {}", brightness(col));
-            (i, col)
+            (point.1, col)
         })
         .filter(|(_, x)| brightness(*x) > info.brightness_thresh)
+        .unzip();
+
+    let (mut found, unpaired) = pairing::pair_colours(&colours);
+    // number the colours in the order the dots are on the board
+    found.sort_by_key(|pair| pair.dots.0.min(pair.dots.1));
+    let pairs: Vec<DotPair> = found
+        .into_iter()
+        .map(|pair| DotPair {
+            ends: (cells[pair.dots.0], cells[pair.dots.1]),
+            confidence: pair.confidence,
+        })
         .collect();
+    let unpaired: Vec<[u8; 2]> = unpaired.into_iter().map(|i| cells[i]).collect();
 
-    let mut grid = Array2D::new(5, 5);
-    let mut pairs = Vec::new();
-    let mut id = 1;
-    loop {
-        if rgbs.len() < 2 {
-            break;
-        }
-        let last_elem = *rgbs.last().unwrap();
+    let mut grid = Array2D::new(info.grid_size.0 as _, info.grid_size.1 as _);
+    let mut colour = |[x, y]: [u8; 2], id| grid[(x as _, y as _)] = Cell::Colour(id);
+    // a cell can only hold so many colours, so any dots past that are left off the grid
+    let mut ids = 1..=u8::MAX;
+    for (pair, id) in pairs.iter().zip(&mut ids) {
+        colour(pair.ends.0, id);
+        colour(pair.ends.1, id);
+    }
+    for (cell, id) in unpaired.iter().zip(ids) {
+        colour(*cell, id);
+    }
+
+    MapLayout {
+        grid,
+        pairs,
+        unpaired,
+    }
+}
 
-        let (removal_index, &pair_index, _) = rgbs
+#[test]
+fn test_hex_lattice() {
+    let mut info = DotLocationInfo {
+        point_locations: Vec::new(),
+        grid_size: (4, 3),
+        dot_size: 5,
+        brightness_thresh: 100,
+        lattice: Lattice::Hex,
+        perspective: None,
+    };
+    let at = |info: &DotLocationInfo, cell: [u8; 2]| {
+        info.point_locations
             .iter()
-            .enumerate()
-            .take(rgbs.len() - 1)
-            .map(|(j, (i, x))| (j, i, pythag(*x, last_elem.1)))
-            .min_by_key(|x| x.2)
-            .unwrap();
-
-        rgbs.remove(removal_index);
-        rgbs.pop();
-
-        let locs = (
-            info.point_locations[last_elem.0].1,
-            info.point_locations[pair_index].1,
-        );
-
-        grid[(locs.0[0] as _, locs.0[1] as _)] = id;
-        grid[(locs.1[0] as _, locs.1[1] as _)] = id;
-        pairs.push((
-            (locs.0[0] as _, locs.0[1] as _),
-            (locs.1[0] as _, locs.1[1] as _),
-        ));
-        id += 1;
-    }
-    if let Some(leftover) = rgbs.get(0) {
-        let locs = info.point_locations[leftover.0].1;
-        grid[(locs[0] as _, locs[1] as _)] = 0xFF;
-    }
-
-    (grid, pairs)
+            .find(|(_, c)| *c == cell)
+            .map(|(screen, _)| *screen)
+    };
+    info.fill_lattice([100, 100], [400, 100], [100, 300]);
+    assert_eq!(info.point_locations.len(), 12);
+    assert_eq!(at(&info, [0, 0]), Some([100, 100]));
+    // odd rows are half a cell to the right
+    assert_eq!(at(&info, [0, 1]), Some([150, 200]));
+    assert_eq!(at(&info, [3, 1]), Some([450, 200]));
+    assert_eq!(at(&info, [2, 2]), Some([300, 300]));
+
+    // with an odd last row the bottom left cell is one of the pushed over ones
+    info.grid_size = (4, 2);
+    info.fill_lattice([100, 100], [400, 100], [150, 200]);
+    assert_eq!(at(&info, [0, 1]), Some([150, 200]));
+    assert_eq!(at(&info, [2, 1]), Some([350, 200]));
+    assert_eq!(at(&info, [2, 0]), Some([300, 100]));
+
+    // and the same cells on a square board line up
+    info.lattice = Lattice::Square;
+    info.fill_lattice([100, 100], [400, 100], [100, 200]);
+    assert_eq!(at(&info, [2, 1]), Some([300, 200]));
 }
@@ -0,0 +1,268 @@
+//! Working out which dots are the two ends of the same path from their colours. Colours
+//! are compared in Lab, where the distance between two of them is roughly how different
+//! they look. Dots are paired up so the colours in each pair are as close as they can be
+//! all together, and a dot left over leaves the colours it looks like unpaired rather
+//! than paired up wrongly.
+
+/// Colours further apart than this are never the same path. Two dots of the same colour
+/// are usually within 10 of each other, and the closest colours the game uses, green and
+/// lime, are nearly 26 apart.
+const MAX_PAIR_DISTANCE: f32 = 20.;
+
+/// Most dots of similar colours that are paired up exactly. Trying every way of pairing
+/// them takes twice as long for each one more, and this many looking alike means the
+/// colours have been read too badly to trust anyway.
+const MAX_GROUP: usize = 16;
+
+/// Two dots of the same colour
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Pair {
+    /// Indices into the colours given to [`pair_colours`]
+    pub dots: (usize, usize),
+    /// How sure the pairing is, from 0 to 1
+    pub confidence: f32,
+}
+
+/// An sRGB colour in CIE Lab, with a D65 white point
+fn lab(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c as f32 / 255.;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let xyz = [
+        (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047,
+        0.2126 * r + 0.7152 * g + 0.0722 * b,
+        (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883,
+    ];
+    let [x, y, z] = xyz.map(|t| {
+        if t > 216. / 24389. {
+            t.cbrt()
+        } else {
+            (24389. / 27. * t + 16.) / 116.
+        }
+    });
+    [116. * y - 16., 500. * (x - y), 200. * (y - z)]
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Pairs up colours, giving the pairs and the indices of any colours left over.
+///
+/// The pairs are the ones with the least distance between their colours in total, where
+/// leaving a colour out counts as half of [`MAX_PAIR_DISTANCE`], so no pair is ever
+/// further apart than that. A colour left out that's close to a pair means which of
+/// them go together can't be told from the colours alone, so that pair is left out too.
+pub(crate) fn pair_colours(colours: &[[u8; 3]]) -> (Vec<Pair>, Vec<usize>) {
+    let labs: Vec<[f32; 3]> = colours.iter().map(|c| lab(*c)).collect();
+    let apart = |a: usize, b: usize| distance(labs[a], labs[b]);
+    let close = |a: usize, b: usize| apart(a, b) <= MAX_PAIR_DISTANCE;
+
+    let mut pairs = Vec::new();
+    let mut unpaired = Vec::new();
+    // pairs further apart than the limit are never used, so colours that aren't
+    // connected by close ones can be paired separately
+    for group in groups(labs.len(), close) {
+        if group.len() > MAX_GROUP {
+            unpaired.extend(group);
+            continue;
+        }
+        let (matched, left) = best_pairs(&group, apart);
+        for (a, b) in matched {
+            if left.iter().any(|c| close(a, *c) || close(b, *c)) {
+                unpaired.extend([a, b]);
+            } else {
+                pairs.push((a, b));
+            }
+        }
+        unpaired.extend(left);
+    }
+    unpaired.sort_unstable();
+
+    let pairs = pairs
+        .into_iter()
+        .map(|(a, b)| {
+            let between = apart(a, b);
+            // as close as the nearest other colour to either of them is no confidence
+            let nearest_other = (0..labs.len())
+                .filter(|c| *c != a && *c != b)
+                .map(|c| apart(a, c).min(apart(b, c)))
+                .fold(f32::INFINITY, f32::min);
+            let confidence = (1. - between / nearest_other)
+                .min(1. - between / MAX_PAIR_DISTANCE)
+                .clamp(0., 1.);
+            Pair {
+                dots: (a, b),
+                confidence,
+            }
+        })
+        .collect();
+    (pairs, unpaired)
+}
+
+/// The pairs of `dots` closest together in total and the dots left out of them, trying
+/// every way of pairing them up
+fn best_pairs(
+    dots: &[usize],
+    apart: impl Fn(usize, usize) -> f32,
+) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let n = dots.len();
+    let alone = MAX_PAIR_DISTANCE / 2.;
+    // the best way to pair up the dots not in each set of them, and the first dot's
+    // partner in it, or itself if it's left out
+    let mut best = vec![(0., 0); 1 << n];
+    for settled in (0..(1 << n) - 1).rev() {
+        let first = (0..n).find(|i| settled & 1 << i == 0).unwrap();
+        let settle = |with: usize| settled | 1 << first | 1 << with;
+        let mut choice = (alone + best[settle(first)].0, first);
+        for other in first + 1..n {
+            if settled & 1 << other == 0 {
+                let cost = apart(dots[first], dots[other]) + best[settle(other)].0;
+                if cost < choice.0 {
+                    choice = (cost, other);
+                }
+            }
+        }
+        best[settled] = choice;
+    }
+
+    let mut pairs = Vec::new();
+    let mut left = Vec::new();
+    let mut settled = 0;
+    while settled != (1 << n) - 1 {
+        let first = (0..n).find(|i| settled & 1 << i == 0).unwrap();
+        let partner = best[settled].1;
+        if partner == first {
+            left.push(dots[first]);
+        } else {
+            pairs.push((dots[first], dots[partner]));
+        }
+        settled |= 1 << first | 1 << partner;
+    }
+    (pairs, left)
+}
+
+/// Splits `0..count` into groups where everything is connected to everything else by
+/// a chain of `close` ones
+fn groups(count: usize, close: impl Fn(usize, usize) -> bool) -> Vec<Vec<usize>> {
+    let mut group_of: Vec<Option<usize>> = vec![None; count];
+    let mut groups = Vec::new();
+    for start in 0..count {
+        if group_of[start].is_some() {
+            continue;
+        }
+        let mut group = vec![start];
+        group_of[start] = Some(groups.len());
+        let mut i = 0;
+        while i < group.len() {
+            let at = group[i];
+            for (next, next_group) in group_of.iter_mut().enumerate() {
+                if next_group.is_none() && close(at, next) {
+                    *next_group = Some(groups.len());
+                    group.push(next);
+                }
+            }
+            i += 1;
+        }
+        group.sort_unstable();
+        groups.push(group);
+    }
+    groups
+}
+
+#[test]
+fn test_pair_colours() {
+    use crate::{draw_board, get_map_layout, DotLocationInfo, Lattice};
+    use read_cam::RgbaView;
+    use solver::{Array2D, Cell};
+
+    let mut info = DotLocationInfo {
+        point_locations: Vec::new(),
+        grid_size: (5, 5),
+        dot_size: 5,
+        brightness_thresh: 100,
+        lattice: Lattice::Square,
+        perspective: None,
+    };
+    info.fill_lattice([20, 20], [180, 20], [20, 180]);
+    let (width, height) = (200, 200);
+    let layout = |dots: &[([u8; 2], u8)]| {
+        let mut grid = Array2D::new(5, 5);
+        for ([x, y], colour) in dots {
+            grid[(*x as _, *y as _)] = Cell::Colour(*colour);
+        }
+        let mut buf = vec![0; width * height * 4];
+        let mut img = RgbaView::new(&mut buf, width, height);
+        draw_board(&info, &grid, &mut img);
+        get_map_layout(&info, &img)
+    };
+
+    // green and lime, and white and wheat, are the closest colours drawn
+    let (green, lime, white, wheat, red) = (2, 12, 10, 13, 1);
+    let mut dots = vec![
+        ([0, 0], green),
+        ([4, 0], green),
+        ([0, 1], lime),
+        ([4, 1], lime),
+        ([0, 2], white),
+        ([4, 2], white),
+        ([0, 3], wheat),
+        ([4, 3], wheat),
+        ([2, 4], red),
+        ([3, 4], red),
+    ];
+    let found = layout(&dots);
+    assert!(found.unpaired.is_empty());
+    let pairs: Vec<_> = found.pairs.iter().map(|pair| pair.ends).collect();
+    assert_eq!(
+        pairs,
+        [
+            ([0, 0], [4, 0]),
+            ([0, 1], [4, 1]),
+            ([0, 2], [4, 2]),
+            ([0, 3], [4, 3]),
+            ([2, 4], [3, 4]),
+        ]
+    );
+    assert!(found.pairs.iter().all(|pair| pair.confidence > 0.9));
+
+    // a third green dot could go with either of the others, so none of them are paired,
+    // but lime still is
+    dots.push(([2, 0], green));
+    let found = layout(&dots);
+    assert_eq!(found.unpaired, [[0, 0], [2, 0], [4, 0]]);
+    assert_eq!(found.pairs.len(), 4);
+    assert_eq!(found.pairs[0].ends, ([0, 1], [4, 1]));
+
+    // colours read a little off still pair with the right ones
+    let (pairs, unpaired) = pair_colours(&[
+        [0, 200, 0],
+        [128, 255, 0],
+        [12, 190, 8],
+        [120, 250, 10],
+        [255, 255, 255],
+        [245, 222, 179],
+        [240, 240, 245],
+        [250, 225, 170],
+    ]);
+    assert!(unpaired.is_empty());
+    let pairs: Vec<_> = pairs.iter().map(|pair| pair.dots).collect();
+    assert_eq!(pairs, [(0, 2), (1, 3), (4, 6), (5, 7)]);
+
+    // two shades of red close enough to all look alike are still two pairs, going by
+    // which are closest
+    let (pairs, unpaired) =
+        pair_colours(&[[200, 40, 40], [225, 70, 60], [203, 44, 42], [228, 74, 62]]);
+    assert!(unpaired.is_empty());
+    let pairs: Vec<_> = pairs.iter().map(|pair| pair.dots).collect();
+    assert_eq!(pairs, [(0, 2), (1, 3)]);
+}
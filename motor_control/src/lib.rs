@@ -1,8 +1,16 @@
 use std::{thread, time::Duration};
 
 use serialport::{Error, SerialPort, TTYPort};
+use solver::Topology;
 
-#[derive(Debug)]
+pub mod trajectory;
+
+use trajectory::GridMap;
+
+/// Longest instruction chain the firmware takes in one go, in bytes
+pub(crate) const CHAIN_LIMIT: usize = 255;
+
+#[derive(Clone, Copy, Debug)]
 pub enum SolvingCommand {
     Goto(u8, u8),
     PenUp,
@@ -17,6 +25,44 @@ impl SolvingCommand {
             Self::PenDown => data.push(2),
         }
     }
+
+    /// How many bytes [`Self::as_bytes`] writes
+    pub fn size(&self) -> usize {
+        let mut data = Vec::new();
+        self.as_bytes(&mut data);
+        data.len()
+    }
+}
+
+/// A command sent to the firmware on its own, with the position worked out here in motor
+/// steps rather than looked up in the firmware's grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbsoluteCommand {
+    Goto(u16, u16),
+    PenUp,
+    PenDown,
+}
+
+/// Splits commands into instruction chains short enough for the firmware, cutting
+/// wherever one gets full
+pub fn split_chains(commands: &[SolvingCommand]) -> Vec<Vec<SolvingCommand>> {
+    pack_chains(commands.iter().map(|command| vec![*command]))
+}
+
+/// Puts pieces together into as few instruction chains as they fit in, without
+/// splitting any piece up
+pub(crate) fn pack_chains(
+    pieces: impl IntoIterator<Item = Vec<SolvingCommand>>,
+) -> Vec<Vec<SolvingCommand>> {
+    let size = |commands: &[SolvingCommand]| commands.iter().map(|c| c.size()).sum::<usize>();
+    let mut chains: Vec<Vec<SolvingCommand>> = Vec::new();
+    for piece in pieces {
+        match chains.last_mut() {
+            Some(chain) if size(chain) + size(&piece) <= CHAIN_LIMIT => chain.extend(piece),
+            _ => chains.push(piece),
+        }
+    }
+    chains
 }
 
 #[repr(u8)]
@@ -68,6 +114,9 @@ impl From<u8> for MotorResponse {
 
 pub struct Motor<S> {
     port: S,
+    /// Where the cells are when the grid isn't 5x5, which is the only size the firmware
+    /// can be told about. Grid moves on any other size are sent as absolute gotos.
+    cells: Option<GridMap>,
 }
 
 impl Motor<TTYPort> {
@@ -75,7 +124,7 @@ impl Motor<TTYPort> {
         let port = serialport::new(port, 9600)
             .timeout(Duration::from_secs(300))
             .open_native()?;
-        Ok(Self { port })
+        Ok(Self { port, cells: None })
     }
 }
 
@@ -127,28 +176,42 @@ where
     }
 
     pub fn goto_grid(&mut self, x: u8, y: u8) -> Result<MotorResponse, Error> {
+        if let Some(cells) = self.cells {
+            let (x, y) = cells.position((x, y));
+            return self.goto(x, y);
+        }
         self.port.write_all(&[MotorCommand::GotoGrid as _, x, y])?;
         println!("Waiting for response...");
         self.wait_response()
     }
 
+    /// Tells the firmware where every cell is. The firmware only holds a 5x5 grid, so
+    /// any other size is kept here instead, and moves on it are sent as absolute gotos.
+    /// Every cell is placed, so hex boards get their odd rows pushed over by `topology`
+    /// without the firmware knowing about it.
     pub fn calibrate_3point(
         &mut self,
         tl: (u16, u16),
         tr: (u16, u16),
         bl: (u16, u16),
+        grid_size: (u8, u8),
+        topology: &'static dyn Topology,
     ) -> Result<MotorResponse, Error> {
         println!("Calibrate: {tl:?}, {tr:?}, {bl:?}");
-        let mut points = [0u16; 25 * 2];
-        let h = (tr.0 - tl.0, tr.1 - tl.1).0 / 4; // leave out y part
-        let v = (bl.0 - tl.0, bl.1 - tl.1).1 / 4; // leave out x part
+        let map = GridMap::from_corners(tl, tr, bl, grid_size, topology);
+        if grid_size != (5, 5) {
+            self.cells = Some(map);
+            return Ok(MotorResponse::Ok);
+        }
+        self.cells = None;
+
+        let mut points = vec![0u16; 5 * 5 * 2];
 
         for (i, p) in points.chunks_mut(2).enumerate() {
-            let x = (i % 5) as u16;
-            let y = (i / 5) as u16;
+            let x = (i % 5) as u8;
+            let y = (i / 5) as u8;
 
-            p[0] = tl.0 + x * h;
-            p[1] = tl.1 + y * v;
+            (p[0], p[1]) = map.position((x, y));
         }
 
         println!("{:?}", points);
@@ -182,30 +245,81 @@ where
         Ok((resp, (u16::from_le_bytes(x), u16::from_le_bytes(y))))
     }
 
+    /// Runs the commands, split into as many chains as it takes to fit the firmware's
+    /// limit. Stops at the first chain that doesn't come back ok.
     pub fn execute_in_order(
         &mut self,
         commands: &[SolvingCommand],
     ) -> Result<MotorResponse, Error> {
-        println!("Executing...");
-        let mut cmds = Vec::new();
-        for cmd in commands.iter() {
-            cmd.as_bytes(&mut cmds);
-        }
+        self.execute_chains(&split_chains(commands))
+    }
 
-        if cmds.len() > 255 {
-            println!("TOO LONG COMMAND CHAIN");
-            return Ok(MotorResponse::Ok);
+    /// Runs each instruction chain in turn, waiting for the firmware to finish one
+    /// before sending the next, so the pen comes to a stop between them. Stops at the
+    /// first chain that doesn't come back ok.
+    ///
+    /// When the grid isn't one the firmware knows, the commands are run one at a time
+    /// with the cells placed here instead.
+    pub fn execute_chains(
+        &mut self,
+        chains: &[Vec<SolvingCommand>],
+    ) -> Result<MotorResponse, Error> {
+        if let Some(cells) = self.cells {
+            let commands: Vec<AbsoluteCommand> = chains
+                .iter()
+                .flatten()
+                .map(|command| match *command {
+                    SolvingCommand::Goto(x, y) => {
+                        let (x, y) = cells.position((x, y));
+                        AbsoluteCommand::Goto(x, y)
+                    }
+                    SolvingCommand::PenUp => AbsoluteCommand::PenUp,
+                    SolvingCommand::PenDown => AbsoluteCommand::PenDown,
+                })
+                .collect();
+            return self.execute_absolute(&commands);
         }
 
-        println!("Instruction count: {}", cmds.len());
-        println!("Instructions: {:?}", cmds);
-
-        self.port
-            .write_all(&[MotorCommand::InstructionChain as u8, cmds.len() as u8])?;
-        thread::sleep(Duration::from_millis(10));
-        self.port.write_all(&cmds)?;
+        println!("Executing...");
+        for chain in chains {
+            let mut cmds = Vec::new();
+            for cmd in chain {
+                cmd.as_bytes(&mut cmds);
+            }
+            println!("Instruction count: {}", cmds.len());
+            println!("Instructions: {:?}", cmds);
+
+            self.port
+                .write_all(&[MotorCommand::InstructionChain as u8, cmds.len() as u8])?;
+            thread::sleep(Duration::from_millis(10));
+            self.port.write_all(&cmds)?;
+
+            let resp = self.wait_response()?;
+            if !resp.ok() {
+                return Ok(resp);
+            }
+        }
+        Ok(MotorResponse::Ok)
+    }
 
-        self.wait_response()
+    /// Runs each command on its own, waiting for the firmware to finish it before
+    /// sending the next. Stops at the first one that doesn't come back ok.
+    pub fn execute_absolute(
+        &mut self,
+        commands: &[AbsoluteCommand],
+    ) -> Result<MotorResponse, Error> {
+        println!("Executing {} commands one at a time...", commands.len());
+        for command in commands {
+            let resp = match *command {
+                AbsoluteCommand::Goto(x, y) => self.goto(x, y)?,
+                AbsoluteCommand::PenUp => self.pen_up()?,
+                AbsoluteCommand::PenDown => self.pen_down()?,
+            };
+            if !resp.ok() {
+                return Ok(resp);
+            }
+        }
+        Ok(MotorResponse::Ok)
     }
 
     pub fn pen_up(&mut self) -> Result<MotorResponse, Error> {
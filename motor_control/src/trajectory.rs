@@ -0,0 +1,376 @@
+//! Smooth motion along the lines the pen draws. Moving to each corner of a line as a
+//! separate [`crate::SolvingCommand::Goto`] turns the pen sharply at every one, so
+//! instead the corners are rounded off and the line is followed as a stream of
+//! [`AbsoluteCommand::Goto`]s in motor steps, spaced out to slow down only as much as
+//! the curves and the motor limits need.
+//!
+//! The firmware finishes each goto before taking the next, so how smoothly the pen
+//! carries on from one point to the next is up to how it speeds up and slows down.
+
+use solver::{IndexTy, Topology};
+
+use crate::AbsoluteCommand;
+
+type Point = (f64, f64);
+
+/// Seconds between the points the pen is sent to, when going as fast as the limits
+/// allow
+pub const SAMPLE_PERIOD: f64 = 0.02;
+
+/// How far from a corner the pen starts turning, in cells. Less than half a cell
+/// keeps the rounded corner inside the corner cell.
+const CORNER_BLEND: f64 = 0.35;
+
+/// Points along each rounded corner
+const CURVE_STEPS: usize = 8;
+
+/// Where each grid cell is in motor steps, worked out from three corners of the grid
+/// the same way the firmware is told by [`crate::Motor::calibrate_3point`]
+#[derive(Clone, Copy, Debug)]
+pub struct GridMap {
+    pub origin: (u16, u16),
+    /// Steps per cell width across and down, which for hex cells is more than the
+    /// steps between rows
+    pub scale: (f64, f64),
+    /// How the cells are laid out, so hex rows can be pushed over
+    pub topology: &'static dyn Topology,
+}
+
+impl GridMap {
+    pub fn from_corners(
+        tl: (u16, u16),
+        tr: (u16, u16),
+        bl: (u16, u16),
+        grid_size: (u8, u8),
+        topology: &'static dyn Topology,
+    ) -> Self {
+        let (width, height) = (grid_size.0 as IndexTy, grid_size.1 as IndexTy);
+        let across = topology.centre(((width - 1).max(0), 0)).0;
+        let down = topology.centre((0, (height - 1).max(0))).1;
+        let per_cell = |from: u16, to: u16, cells: f64| {
+            (to as f64 - from as f64) / if cells > 0. { cells } else { 1. }
+        };
+        Self {
+            origin: tl,
+            // leave out the y part going across and the x part going down
+            scale: (per_cell(tl.0, tr.0, across), per_cell(tl.1, bl.1, down)),
+            topology,
+        }
+    }
+
+    pub fn position(&self, (x, y): (u8, u8)) -> (u16, u16) {
+        let (across, down) = self.topology.centre((x as IndexTy, y as IndexTy));
+        (
+            (self.origin.0 as f64 + across * self.scale.0).round() as u16,
+            (self.origin.1 as f64 + down * self.scale.1).round() as u16,
+        )
+    }
+
+    /// The cell nearest to a position in steps, which can be outside the grid
+    pub fn nearest_cell(&self, (x, y): (u16, u16)) -> (i32, i32) {
+        let at = (
+            (x as f64 - self.origin.0 as f64) / self.scale.0.max(1.),
+            (y as f64 - self.origin.1 as f64) / self.scale.1.max(1.),
+        );
+        let row_pitch = self.topology.centre((0, 1)).1;
+        let row = (at.1 / row_pitch).round() as IndexTy;
+        // the nearest row going by height alone can be the wrong one for a cell in
+        // a pushed over row, so try the ones either side too
+        (row - 1..=row + 1)
+            .flat_map(|y| {
+                let column = (at.0 - self.topology.centre((0, y)).0).round() as IndexTy;
+                (column - 1..=column + 1).map(move |x| (x, y))
+            })
+            .min_by(|a, b| {
+                let apart = |cell| {
+                    let (cx, cy) = self.topology.centre(cell);
+                    (cx - at.0).hypot(cy - at.1)
+                };
+                apart(*a).total_cmp(&apart(*b))
+            })
+            .map(|(x, y)| (x as i32, y as i32))
+            .unwrap()
+    }
+}
+
+/// How fast the pen can go, in steps per second and steps per second squared like
+/// [`crate::Motor::set_motor_speed`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    pub speed: f64,
+    pub accel: f64,
+}
+
+impl Limits {
+    pub fn new(speed: i32, accel: i32) -> Self {
+        Self {
+            speed: speed.max(1) as f64,
+            accel: accel.max(1) as f64,
+        }
+    }
+}
+
+/// Commands drawing each line through the cells given, lifting the pen in between,
+/// for [`crate::Motor::execute_absolute`]. Getting to the start of a line doesn't need
+/// to be smooth, so that's a single goto.
+pub fn draw(lines: &[Vec<(u8, u8)>], map: &GridMap, limits: &Limits) -> Vec<AbsoluteCommand> {
+    use AbsoluteCommand::{Goto, PenDown, PenUp};
+
+    let blend = CORNER_BLEND * map.scale.0.min(map.scale.1);
+    let mut commands = Vec::new();
+    for line in lines {
+        let corners: Vec<Point> = line
+            .iter()
+            .map(|cell| {
+                let (x, y) = map.position(*cell);
+                (x as f64, y as f64)
+            })
+            .collect();
+        let points = follow(&round_corners(&corners, blend), limits);
+        let mut points = points
+            .into_iter()
+            .map(|(x, y)| Goto(x.round() as u16, y.round() as u16));
+        let Some(start) = points.next() else {
+            continue;
+        };
+        commands.extend([PenUp, start, PenDown]);
+        commands.extend(points);
+    }
+    commands.push(PenUp);
+    commands
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+fn lerp(a: Point, b: Point, t: f64) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// Replaces each corner with a curve starting `blend` before it and ending `blend`
+/// after, or halfway along the sides if they're shorter than that
+fn round_corners(corners: &[Point], blend: f64) -> Vec<Point> {
+    let Some(&first) = corners.first() else {
+        return Vec::new();
+    };
+    let mut path = vec![first];
+    for corner in corners.windows(3) {
+        let [before, at, after] = [corner[0], corner[1], corner[2]];
+        let into = distance(before, at);
+        let out = distance(at, after);
+        if into == 0.0 || out == 0.0 {
+            path.push(at);
+            continue;
+        }
+        let start = lerp(at, before, blend.min(into / 2.0) / into);
+        let end = lerp(at, after, blend.min(out / 2.0) / out);
+        // quadratic bezier with the corner as its control point
+        for i in 0..=CURVE_STEPS {
+            let t = i as f64 / CURVE_STEPS as f64;
+            path.push(lerp(lerp(start, at, t), lerp(at, end, t), t));
+        }
+    }
+    if corners.len() > 1 {
+        path.push(corners[corners.len() - 1]);
+    }
+    path
+}
+
+/// Where the pen should be every [`SAMPLE_PERIOD`] to follow the path as fast as the
+/// limits allow, starting and finishing at rest
+fn follow(path: &[Point], limits: &Limits) -> Vec<Point> {
+    // split the path into pieces short enough that speeding up from a stop over one of
+    // them isn't held back much by where the points happen to be
+    let piece = limits.speed * SAMPLE_PERIOD / 4.0;
+    let mut points: Vec<Point> = path.first().copied().into_iter().collect();
+    for pair in path.windows(2) {
+        let pieces = (distance(pair[0], pair[1]) / piece).ceil().max(1.0) as usize;
+        for i in 1..=pieces {
+            points.push(lerp(pair[0], pair[1], i as f64 / pieces as f64));
+        }
+    }
+    points.dedup();
+    if points.len() < 2 {
+        return points;
+    }
+    let n = points.len();
+    let lengths: Vec<f64> = points.windows(2).map(|p| distance(p[0], p[1])).collect();
+
+    // going round a curve of radius r takes v²/r of acceleration
+    let mut speed = vec![limits.speed; n];
+    (speed[0], speed[n - 1]) = (0.0, 0.0);
+    for i in 1..n - 1 {
+        let (a, b, c) = (points[i - 1], points[i], points[i + 1]);
+        let turn = ((c.1 - b.1).atan2(c.0 - b.0) - (b.1 - a.1).atan2(b.0 - a.0))
+            .rem_euclid(std::f64::consts::TAU);
+        let turn = turn.min(std::f64::consts::TAU - turn);
+        if turn > 1e-9 {
+            let radius = (lengths[i - 1] + lengths[i]) / 2.0 / turn;
+            speed[i] = speed[i].min((limits.accel * radius).sqrt());
+        }
+    }
+    // then limit how quickly that can change, speeding up forwards and slowing down
+    // backwards
+    for i in 1..n {
+        speed[i] =
+            speed[i].min((speed[i - 1].powi(2) + 2.0 * limits.accel * lengths[i - 1]).sqrt());
+    }
+    for i in (0..n - 1).rev() {
+        speed[i] = speed[i].min((speed[i + 1].powi(2) + 2.0 * limits.accel * lengths[i]).sqrt());
+    }
+
+    // steady acceleration over each piece takes it at the average of its end speeds
+    let mut times = vec![0.0; n];
+    for i in 1..n {
+        times[i] = times[i - 1] + 2.0 * lengths[i - 1] / (speed[i - 1] + speed[i]);
+    }
+
+    let mut samples = Vec::new();
+    let mut i = 0;
+    let mut time = 0.0;
+    while time < times[n - 1] {
+        while times[i + 1] < time {
+            i += 1;
+        }
+        let (v, dt) = (speed[i], time - times[i]);
+        let accel = (speed[i + 1].powi(2) - v.powi(2)) / (2.0 * lengths[i]);
+        let along = (v * dt + accel * dt * dt / 2.0) / lengths[i];
+        samples.push(lerp(points[i], points[i + 1], along.min(1.0)));
+        time += SAMPLE_PERIOD;
+    }
+    samples.push(points[n - 1]);
+    samples
+}
+
+#[test]
+fn test_round_corners() {
+    let path = round_corners(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], 3.0);
+    assert_eq!(path.first(), Some(&(0.0, 0.0)));
+    assert_eq!(path.last(), Some(&(10.0, 10.0)));
+    // the curve starts and ends 3 away from the corner without reaching it
+    assert!(path.contains(&(7.0, 0.0)) && path.contains(&(10.0, 3.0)));
+    assert!(!path.contains(&(10.0, 0.0)));
+    assert!(path
+        .iter()
+        .all(|p| distance(*p, (10.0, 0.0)) <= 3.0 || p.0 == 0.0 || p.1 == 10.0));
+
+    // short sides only get rounded halfway along
+    let path = round_corners(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0)], 3.0);
+    assert!(path.contains(&(1.0, 0.0)) && path.contains(&(2.0, 1.0)));
+}
+
+#[test]
+fn test_follow() {
+    let limits = Limits::new(1000, 5000);
+    let straight = follow(&[(0.0, 0.0), (1000.0, 0.0)], &limits);
+    let moved: Vec<f64> = straight.windows(2).map(|p| distance(p[0], p[1])).collect();
+    assert_eq!(straight.last(), Some(&(1000.0, 0.0)));
+    // never faster than the speed limit, and never changing speed faster than allowed
+    let most = limits.speed * SAMPLE_PERIOD;
+    assert!(moved.iter().all(|d| *d <= most + 1e-6));
+    let most_change = limits.accel * SAMPLE_PERIOD * SAMPLE_PERIOD;
+    // apart from the last, which stops partway through
+    let steady = &moved[..moved.len() - 1];
+    assert!(steady
+        .windows(2)
+        .all(|d| (d[1] - d[0]).abs() <= most_change + 1e-6));
+    // and getting up to full speed in the middle
+    assert!(moved[moved.len() / 2] > most * 0.99);
+
+    // a sharp corner slows it down without stopping
+    let corner = round_corners(&[(0.0, 0.0), (500.0, 0.0), (500.0, 500.0)], 50.0);
+    let around = follow(&corner, &limits);
+    let moved: Vec<f64> = around.windows(2).map(|p| distance(p[0], p[1])).collect();
+    let at_corner = around
+        .iter()
+        .position(|p| p.0 > 490.0 && p.1 > 10.0)
+        .unwrap();
+    assert!(moved[at_corner] < most * 0.9);
+    assert!(moved[at_corner - 2..at_corner + 2].iter().all(|d| *d > 0.0));
+}
+
+#[test]
+fn test_hex_map() {
+    // 87 steps between rows is a cell width of 100
+    let map = GridMap::from_corners((100, 100), (500, 100), (100, 448), (5, 5), &solver::Hex);
+    assert!((map.scale.0 - 100.0).abs() < 1e-9 && (map.scale.1 - 100.0).abs() < 0.5);
+    assert_eq!(map.position((0, 0)), (100, 100));
+    // odd rows are half a cell over
+    assert_eq!(map.position((0, 1)), (150, 187));
+    assert_eq!(map.position((2, 1)), (350, 187));
+    assert_eq!(map.position((2, 2)), (300, 274));
+    for y in 0..5 {
+        for x in 0..5 {
+            let (px, py) = map.position((x, y));
+            assert_eq!(map.nearest_cell((px, py)), (x as i32, y as i32));
+            assert_eq!(map.nearest_cell((px + 30, py - 30)), (x as i32, y as i32));
+        }
+    }
+    // between two rows it goes by how far across it is
+    assert_eq!(map.nearest_cell((170, 143)), (0, 1));
+    assert_eq!(map.nearest_cell((120, 143)), (0, 0));
+}
+
+#[test]
+fn test_draw() {
+    use AbsoluteCommand::{Goto, PenDown, PenUp};
+
+    let map = GridMap::from_corners((100, 100), (500, 100), (100, 500), (5, 5), &solver::Square);
+    assert_eq!(map.scale, (100.0, 100.0));
+    assert_eq!(map.position((2, 3)), (300, 400));
+    assert_eq!(map.nearest_cell((340, 460)), (2, 4));
+    assert_eq!(map.nearest_cell((0, 100)), (-1, 0));
+
+    let commands = draw(
+        &[vec![(0, 0), (2, 0), (2, 2)], vec![(4, 4)]],
+        &map,
+        &Limits::new(5000, 20000),
+    );
+    let gotos = commands.iter().filter(|c| matches!(c, Goto(..))).count();
+    assert!(gotos > 5);
+    assert_eq!(commands[..3], [PenUp, Goto(100, 100), PenDown]);
+    assert_eq!(
+        commands[commands.len() - 5..],
+        [Goto(300, 300), PenUp, Goto(500, 500), PenDown, PenUp]
+    );
+}
+
+#[test]
+fn test_long_lines() {
+    let map = GridMap::from_corners((100, 100), (500, 100), (100, 500), (5, 5), &solver::Square);
+    let limits = Limits::new(500, 2000);
+    let commands = draw(
+        &[vec![(0, 0), (4, 0), (4, 4), (0, 4)], vec![(0, 2), (4, 2)]],
+        &map,
+        &limits,
+    );
+
+    // every line is one stream of gotos however long it is, never further apart than
+    // the pen can go between them, and starting and finishing slowly
+    let lines: Vec<Vec<Point>> = commands
+        .split(|c| *c == AbsoluteCommand::PenDown)
+        .skip(1)
+        .map(|line| {
+            line.iter()
+                .map_while(|c| match *c {
+                    AbsoluteCommand::Goto(x, y) => Some((x as f64, y as f64)),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    assert_eq!(lines.len(), 2);
+    let most = limits.speed * SAMPLE_PERIOD + 1.0;
+    let slow = limits.speed * SAMPLE_PERIOD / 4.0;
+    for line in &lines {
+        let moved: Vec<f64> = line.windows(2).map(|p| distance(p[0], p[1])).collect();
+        assert!(moved.iter().all(|d| *d <= most));
+        assert!(moved[0] < slow && moved[moved.len() - 1] < slow);
+    }
+
+    // and goes through the corners that aren't rounded off
+    for end in [(100, 100), (500, 300), (100, 500)] {
+        assert!(commands.contains(&AbsoluteCommand::Goto(end.0, end.1)));
+    }
+}
@@ -1,84 +1,136 @@
-use solver::Array2D;
+use solver::{Array2D, Board};
 
+mod order;
+mod simulate;
+mod swipe;
 #[cfg(test)]
 mod tests;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum Instruction {
-    Up,
-    Down,
-    Left,
-    Right,
-    PenUp,
-    PenDown,
-    Goto(u8, u8),
-    ToViewArea,
+pub use simulate::{pen_moves, simulate, Line, Pen, Problem, Simulation};
+pub use swipe::{gesture_strokes, swipe, Gesture};
+
+/// One line the pen goes along without going up or down, straight from each vertex to
+/// the next. Following the strokes in order is the whole drawing, which is what
+/// [`pen_moves`] turns them into, and the pen is lifted to get to the start of any
+/// stroke that doesn't start where the last one ended.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Stroke {
+    /// The colour drawn, or 0 if the pen is up
+    pub colour: u8,
+    /// The cells at either end of the line and every cell where it turns
+    pub vertices: Vec<(u8, u8)>,
+    /// Whether the pen is pressed down the whole way, drawing the line, or lifted to
+    /// move between lines
+    pub pen_down: bool,
+}
+
+fn cell((x, y): (isize, isize)) -> (u8, u8) {
+    (x as u8, y as u8)
 }
 
-fn find_start(unsolvied_grid: &Array2D, pos: (isize, isize)) -> Option<(u8, isize, isize)> {
-    let mut best = None;
-    for i in 0..5 {
-        for j in 0..5 {
-            if unsolvied_grid[(i, j)] != 0 {
-                let dist = (i - pos.0).pow(2) + (j - pos.1).pow(2);
-                match best {
-                    None => best = Some((dist, (unsolvied_grid[(i, j)], i, j))),
-                    Some((score, _)) => {
-                        if score > dist {
-                            best = Some((dist, (unsolvied_grid[(i, j)], i, j)));
-                        }
-                    }
+/// Strokes going through `cells` in order, which are the cells of one path. Going
+/// through a warp means lifting the pen, so that splits it into more than one stroke.
+fn trace(board: &Board, colour: u8, cells: &[(isize, isize)]) -> Vec<Stroke> {
+    let mut strokes = Vec::new();
+    let Some(&first) = cells.first() else {
+        return strokes;
+    };
+    let mut vertices = vec![cell(first)];
+    let mut heading = None;
+
+    for step in cells.windows(2) {
+        let (pos, next) = (step[0], step[1]);
+        // a warp can come out next to where it went in, so an ordinary step is taken
+        // over one through a warp
+        let side = board
+            .topology()
+            .sides()
+            .iter()
+            .copied()
+            .filter(|side| board.step(pos, *side).is_some_and(|(to, _)| to == next))
+            .min_by_key(|side| board.is_warp(pos, *side));
+
+        match side {
+            Some(side) if !board.is_warp(pos, side) => {
+                // only corners are kept, straight runs are drawn in one go
+                if heading.is_some_and(|heading| heading != side) {
+                    vertices.push(cell(pos));
+                }
+                heading = Some(side);
+            }
+            _ => {
+                if vertices.last() != Some(&cell(pos)) {
+                    vertices.push(cell(pos));
                 }
+                strokes.push(Stroke {
+                    colour,
+                    vertices: std::mem::replace(&mut vertices, vec![cell(next)]),
+                    pen_down: true,
+                });
+                heading = None;
             }
         }
     }
-    best.map(|x| x.1)
-}
 
-fn iterate_around(x: isize, y: isize) -> impl Iterator<Item = ((isize, isize), Instruction)> {
-    use Instruction::*;
-    [
-        ((x + 1, y), Right),
-        ((x - 1, y), Left),
-        ((x, y + 1), Down),
-        ((x, y - 1), Up),
-    ]
-    .into_iter()
-    .filter(|((x, y), _)| *x >= 0 && *y >= 0 && *x < 5 && *y < 5)
+    let last = cell(cells[cells.len() - 1]);
+    if vertices.last() != Some(&last) {
+        vertices.push(last);
+    }
+    strokes.push(Stroke {
+        colour,
+        vertices,
+        pen_down: true,
+    });
+    strokes
 }
 
-pub fn pathfind(mut unsolvied_grid: Array2D, solved_grid: &Array2D) -> Vec<Instruction> {
-    let mut instructions = Vec::new();
-    let mut start_pos = (4, 4);
+/// Strokes drawing every path of the solution, in whichever order and direction keeps
+/// the pen moving between them the least, starting from the bottom right corner. Each
+/// line drawn after the first has a stroke with the pen up before it, coming from the
+/// end of the one before.
+///
+/// The paths are the ones [`solver::trace_paths`] finds, so there are no strokes at
+/// all for a grid that isn't a solution to the board.
+pub fn pathfind(board: impl Into<Board>, solved_grid: &Array2D) -> Vec<Stroke> {
+    let board = board.into();
+    let topology = board.topology();
+    let Ok(paths) = solver::trace_paths(board.clone(), solved_grid) else {
+        return Vec::new();
+    };
 
-    loop {
-        let (col, mut x, mut y) = match find_start(&unsolvied_grid, start_pos) {
-            Some(a) => a,
-            None => break,
-        };
-        unsolvied_grid[(x, y)] = 0;
-        let (mut old_x, mut old_y) = (-1, -1);
+    let ends: Vec<_> = paths
+        .iter()
+        .map(|path| {
+            let (first, last) = (path.cells[0], path.cells[path.cells.len() - 1]);
+            [topology.centre(first), topology.centre(last)]
+        })
+        .collect();
+    let corner = (
+        solved_grid.width() as isize - 1,
+        solved_grid.height() as isize - 1,
+    );
 
-        instructions.push(Instruction::Goto(x as u8, y as u8));
-        loop {
-            if let Some(((newx, newy), dir)) = iterate_around(x, y)
-                .filter(|(x, _)| solved_grid[*x] == col)
-                .filter(|(point, _)| *point != (old_x, old_y))
-                .next()
-            {
-                old_x = x;
-                old_y = y;
-                x = newx;
-                y = newy;
-
-                instructions.push(dir);
-            } else {
-                unsolvied_grid[(x, y)] = 0;
-                start_pos = (x, y);
-                break;
+    let lines = order::plan(topology.centre(corner), &ends)
+        .into_iter()
+        .flat_map(|(i, backwards)| {
+            let path = &paths[i];
+            let mut cells = path.cells.clone();
+            if backwards {
+                cells.reverse();
             }
+            trace(&board, path.colour, &cells)
+        });
+
+    let mut strokes: Vec<Stroke> = Vec::new();
+    for line in lines {
+        if let Some(&end) = strokes.last().and_then(|s| s.vertices.last()) {
+            strokes.push(Stroke {
+                colour: 0,
+                vertices: vec![end, line.vertices[0]],
+                pen_down: false,
+            });
         }
+        strokes.push(line);
     }
-
-    instructions
+    strokes
 }
@@ -0,0 +1,149 @@
+//! Which order to draw the paths in and which end to start each one from, so the pen
+//! spends as little time as possible moving between them with nothing to draw
+
+type Point = (f64, f64);
+
+/// Up to this many strokes are ordered exactly, which takes time and memory doubling
+/// with each one
+const EXACT_LIMIT: usize = 12;
+
+/// A stroke to draw, and whether to draw it backwards from its second end
+pub(crate) type Order = Vec<(usize, bool)>;
+
+/// Orders the strokes, given by their two ends, to keep the total distance from the end
+/// of each one to the start of the next short, with the pen starting at `pen`
+pub(crate) fn plan(pen: Point, strokes: &[[Point; 2]]) -> Order {
+    if strokes.len() <= EXACT_LIMIT {
+        exact(pen, strokes)
+    } else {
+        improve(pen, strokes, nearest(pen, strokes))
+    }
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+fn start(strokes: &[[Point; 2]], (stroke, backwards): (usize, bool)) -> Point {
+    strokes[stroke][backwards as usize]
+}
+
+fn end(strokes: &[[Point; 2]], (stroke, backwards): (usize, bool)) -> Point {
+    strokes[stroke][!backwards as usize]
+}
+
+/// How far the pen moves between strokes
+#[cfg(test)]
+pub(crate) fn travel(mut pen: Point, strokes: &[[Point; 2]], order: &[(usize, bool)]) -> f64 {
+    let mut total = 0.0;
+    for &stroke in order {
+        total += distance(pen, start(strokes, stroke));
+        pen = end(strokes, stroke);
+    }
+    total
+}
+
+/// Finds the best order by working out the shortest way to draw every subset of the
+/// strokes, for each stroke it could finish on and which way that was drawn
+pub(crate) fn exact(pen: Point, strokes: &[[Point; 2]]) -> Order {
+    let n = strokes.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    // a state is a stroke drawn a particular way, `stroke * 2 + backwards`
+    let states = n * 2;
+    let index = |set: usize, state: usize| set * states + state;
+    let mut best = vec![f64::INFINITY; (1 << n) * states];
+    let mut from = vec![usize::MAX; (1 << n) * states];
+
+    for state in 0..states {
+        best[index(1 << (state / 2), state)] = distance(pen, strokes[state / 2][state % 2]);
+    }
+    for set in 1..1 << n {
+        for last in 0..states {
+            let cost = best[index(set, last)];
+            if cost == f64::INFINITY {
+                continue;
+            }
+            let pos = strokes[last / 2][1 - last % 2];
+            for next in (0..states).filter(|s| set & 1 << (s / 2) == 0) {
+                let i = index(set | 1 << (next / 2), next);
+                let cost = cost + distance(pos, strokes[next / 2][next % 2]);
+                if cost < best[i] {
+                    best[i] = cost;
+                    from[i] = last;
+                }
+            }
+        }
+    }
+
+    let mut set = (1 << n) - 1;
+    let mut last = (0..states)
+        .min_by(|a, b| best[index(set, *a)].total_cmp(&best[index(set, *b)]))
+        .unwrap();
+    let mut order = Vec::with_capacity(n);
+    loop {
+        order.push((last / 2, last % 2 == 1));
+        let prev = from[index(set, last)];
+        set &= !(1 << (last / 2));
+        if set == 0 {
+            break;
+        }
+        last = prev;
+    }
+    order.reverse();
+    order
+}
+
+/// Always goes to whichever end of a stroke is closest
+pub(crate) fn nearest(mut pen: Point, strokes: &[[Point; 2]]) -> Order {
+    let mut left: Vec<usize> = (0..strokes.len()).collect();
+    let mut order = Vec::with_capacity(strokes.len());
+    while !left.is_empty() {
+        let (i, backwards) = (0..left.len())
+            .flat_map(|i| [(i, false), (i, true)])
+            .min_by(|a, b| {
+                let a = distance(pen, start(strokes, (left[a.0], a.1)));
+                let b = distance(pen, start(strokes, (left[b.0], b.1)));
+                a.total_cmp(&b)
+            })
+            .unwrap();
+        let stroke = (left.remove(i), backwards);
+        pen = end(strokes, stroke);
+        order.push(stroke);
+    }
+    order
+}
+
+/// Reverses runs of strokes, each of them drawn the other way round, for as long as
+/// that makes the order shorter. A run of one just draws the stroke backwards.
+pub(crate) fn improve(pen: Point, strokes: &[[Point; 2]], mut order: Order) -> Order {
+    let n = order.len();
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..n {
+            for j in i..n {
+                let before = if i == 0 {
+                    pen
+                } else {
+                    end(strokes, order[i - 1])
+                };
+                let first = start(strokes, order[i]);
+                let last = end(strokes, order[j]);
+                let after = order.get(j + 1).map(|s| start(strokes, *s));
+
+                let old = distance(before, first) + after.map_or(0.0, |a| distance(last, a));
+                let new = distance(before, last) + after.map_or(0.0, |a| distance(first, a));
+                if new < old - 1e-9 {
+                    order[i..=j].reverse();
+                    for stroke in &mut order[i..=j] {
+                        stroke.1 = !stroke.1;
+                    }
+                    improved = true;
+                }
+            }
+        }
+    }
+    order
+}
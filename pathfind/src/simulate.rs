@@ -0,0 +1,285 @@
+//! Replaying what the pen is told to do against the board, to catch anything that would
+//! draw the wrong thing before the motors get to do it
+
+use std::{collections::HashSet, fmt::Display};
+
+use solver::{Array2D, Board, Cell, IndexTy};
+
+use crate::Stroke;
+
+type Pos = (IndexTy, IndexTy);
+
+/// Something the pen is told to do, with positions in grid cells
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Pen {
+    Up,
+    Down,
+    /// Moves straight to the cell, drawing on the way if the pen is down
+    Goto(Pos),
+}
+
+/// The pen moves following the strokes, lifting it to get to any stroke that doesn't
+/// start where the pen is, and at the end
+pub fn pen_moves(strokes: &[Stroke]) -> Vec<Pen> {
+    let goto = |(x, y): (u8, u8)| Pen::Goto((x as IndexTy, y as IndexTy));
+    let mut moves = Vec::new();
+    let mut at = None;
+    let mut down = None;
+    for stroke in strokes {
+        let Some(&start) = stroke.vertices.first() else {
+            continue;
+        };
+        if at != Some(start) {
+            if down != Some(false) {
+                moves.push(Pen::Up);
+            }
+            moves.push(goto(start));
+            down = Some(false);
+        }
+        if down != Some(stroke.pen_down) {
+            moves.push(if stroke.pen_down { Pen::Down } else { Pen::Up });
+            down = Some(stroke.pen_down);
+        }
+        moves.extend(stroke.vertices[1..].iter().map(|v| goto(*v)));
+        at = stroke.vertices.last().copied();
+    }
+    if down != Some(false) {
+        moves.push(Pen::Up);
+    }
+    moves
+}
+
+/// Something the pen did that it shouldn't have
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Problem {
+    /// The pen was sent outside the grid
+    OffGrid(Pos),
+    /// The pen was put down somewhere other than the end of a path, or where a path it
+    /// already drew comes out of a warp
+    OffEnd(Pos),
+    /// The pen drew `colour` over a cell the solution doesn't have that colour in. It's
+    /// 0 if the pen was put down somewhere with no colour at all.
+    Overdraw { colour: u8, at: Pos },
+    /// A colour the solution has in the cell that was never drawn there
+    Missed { colour: u8, at: Pos },
+}
+
+impl Problem {
+    /// Where it went wrong
+    pub fn at(&self) -> Pos {
+        match *self {
+            Problem::OffGrid(at) | Problem::OffEnd(at) => at,
+            Problem::Overdraw { at, .. } | Problem::Missed { at, .. } => at,
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::OffGrid(at) => write!(f, "the pen goes off the grid to {at:?}"),
+            Problem::OffEnd(at) => write!(f, "the pen is put down at {at:?}, not on an end"),
+            Problem::Overdraw { colour, at } => write!(f, "colour {colour} is drawn over {at:?}"),
+            Problem::Missed { colour, at } => write!(f, "colour {colour} is never drawn at {at:?}"),
+        }
+    }
+}
+
+/// A straight move of the pen from the middle of one cell to another
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Line {
+    pub from: Pos,
+    pub to: Pos,
+    /// The colour being drawn, or `None` if the pen is up
+    pub colour: Option<u8>,
+}
+
+/// Everything the pen did, and what went wrong
+#[derive(Debug, Default, Clone)]
+pub struct Simulation {
+    pub lines: Vec<Line>,
+    pub problems: Vec<Problem>,
+}
+
+impl Simulation {
+    /// Whether the moves draw exactly the solution
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Colours the solution has in a cell, both of them for a bridge
+fn colours(cell: Cell) -> Vec<u8> {
+    match cell {
+        Cell::Colour(colour) => vec![colour],
+        Cell::Bridge {
+            horizontal,
+            vertical,
+        } => vec![horizontal, vertical],
+        Cell::Empty | Cell::Wall => Vec::new(),
+    }
+}
+
+/// The state of the board as the moves are replayed
+struct Replay<'a> {
+    board: &'a Board,
+    solved_grid: &'a Array2D,
+    cells: Vec<Pos>,
+    /// Each cell drawn over, and the colour it was drawn in
+    drawn: HashSet<(Pos, u8)>,
+    problems: Vec<Problem>,
+}
+
+impl Replay<'_> {
+    fn report(&mut self, problem: Problem) {
+        if !self.problems.contains(&problem) {
+            self.problems.push(problem);
+        }
+    }
+
+    fn on_grid(&self, pos: Pos) -> bool {
+        self.board.grid.get(pos).is_some()
+    }
+
+    /// The cells a line from the middle of one cell to another goes over, in order
+    fn crossed(&self, from: Pos, to: Pos) -> Vec<Pos> {
+        let topology = self.board.topology();
+        let (a, b) = (topology.centre(from), topology.centre(to));
+        let samples = ((a.0 - b.0).hypot(a.1 - b.1) * 4.0).ceil().max(1.0) as usize;
+        let mut crossed: Vec<Pos> = Vec::new();
+        for i in 0..=samples {
+            let t = i as f64 / samples as f64;
+            let p = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            let distance = |cell: &Pos| {
+                let c = topology.centre(*cell);
+                (c.0 - p.0).hypot(c.1 - p.1)
+            };
+            let nearest = self
+                .cells
+                .iter()
+                .min_by(|x, y| distance(x).total_cmp(&distance(y)));
+            if let Some(&cell) = nearest.filter(|c| crossed.last() != Some(c)) {
+                crossed.push(cell);
+            }
+        }
+        crossed
+    }
+
+    fn draw(&mut self, cell: Pos, colour: u8) {
+        if !colours(self.solved_grid[cell]).contains(&colour) {
+            self.report(Problem::Overdraw { colour, at: cell });
+        }
+        self.drawn.insert((cell, colour));
+    }
+
+    /// Whether every cell of the colour has been drawn
+    fn finished(&self, colour: u8) -> bool {
+        (self.solved_grid.cells()).all(|(pos, cell)| {
+            !colours(*cell).contains(&colour) || self.drawn.contains(&(pos, colour))
+        })
+    }
+
+    /// Whether the pen going over `pos` while drawing `colour` starts drawing the colour
+    /// whose end it is instead, which a stylus does once its own colour is finished
+    fn picks_up(&self, pos: Pos, colour: u8) -> bool {
+        match self.board.grid.get(pos) {
+            Some(&Cell::Colour(end)) => end != colour && colour != 0 && self.finished(colour),
+            _ => false,
+        }
+    }
+
+    /// Puts the pen down at `pos`, giving the colour it'll draw
+    fn put_down(&mut self, pos: Pos) -> u8 {
+        if !self.on_grid(pos) {
+            return 0;
+        }
+        let colour = colours(self.solved_grid[pos]).first().copied().unwrap_or(0);
+        let on_end = self.board.grid[pos] == Cell::Colour(colour);
+        let through_warp = self.board.topology().sides().iter().any(|&side| {
+            self.board.is_warp(pos, side)
+                && self
+                    .board
+                    .step(pos, side)
+                    .is_some_and(|(from, _)| self.drawn.contains(&(from, colour)))
+        });
+        if !(on_end || through_warp) {
+            self.report(Problem::OffEnd(pos));
+        }
+        self.draw(pos, colour);
+        colour
+    }
+}
+
+/// Replays the moves, starting with the pen up and nowhere in particular, and checks they
+/// draw `solved_grid` without going over anything they shouldn't. Like a stylus on a
+/// touch screen, the pen picks up the colour of an end it's dragged onto once the
+/// colour it was drawing is finished.
+pub fn simulate(board: impl Into<Board>, solved_grid: &Array2D, moves: &[Pen]) -> Simulation {
+    let board = board.into();
+    let mut replay = Replay {
+        board: &board,
+        solved_grid,
+        cells: board.grid.cells().map(|(pos, _)| pos).collect(),
+        drawn: HashSet::new(),
+        problems: Vec::new(),
+    };
+    let mut lines = Vec::new();
+    let mut at: Option<Pos> = None;
+    let mut down = false;
+    // what the pen is drawing, once it's down somewhere
+    let mut colour = None;
+
+    for &pen in moves {
+        match pen {
+            Pen::Up => {
+                down = false;
+                colour = None;
+            }
+            Pen::Down if !down => {
+                down = true;
+                colour = at.map(|pos| replay.put_down(pos));
+            }
+            Pen::Down => (),
+            Pen::Goto(to) => {
+                if !replay.on_grid(to) {
+                    replay.report(Problem::OffGrid(to));
+                }
+                match (at, colour) {
+                    (Some(from), Some(mut drawing)) => {
+                        for cell in replay.crossed(from, to) {
+                            if replay.picks_up(cell, drawing) {
+                                drawing = replay.put_down(cell);
+                            } else {
+                                replay.draw(cell, drawing);
+                            }
+                        }
+                        colour = Some(drawing);
+                    }
+                    // put down before going anywhere, so it starts drawing here
+                    (None, None) if down => colour = Some(replay.put_down(to)),
+                    _ => (),
+                }
+                if let Some(from) = at {
+                    lines.push(Line {
+                        from,
+                        to,
+                        colour: colour.filter(|_| down),
+                    });
+                }
+                at = Some(to);
+            }
+        }
+    }
+
+    for (pos, cell) in solved_grid.cells() {
+        for colour in colours(*cell) {
+            if !replay.drawn.contains(&(pos, colour)) {
+                replay.report(Problem::Missed { colour, at: pos });
+            }
+        }
+    }
+    Simulation {
+        lines,
+        problems: replay.problems,
+    }
+}
@@ -0,0 +1,268 @@
+//! Drawing on a touch screen, where a path is drawn by dragging the stylus from one of
+//! its ends. The stylus can only stay down while it's over cells of the colour being
+//! drawn, so going through a warp doesn't always need another press if the stylus can
+//! slide back over the path to where it comes out. Once a colour is finished the
+//! stylus can also slide over it onto an end of the next colour and carry on drawing
+//! that one without being lifted at all.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use solver::{Array2D, Board, Cell, IndexTy, Side};
+
+use crate::{cell, pathfind, Stroke};
+
+/// Everything drawn in one colour from `start`, which is where the stylus is pressed
+/// down unless it's `held` there from the gesture before
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Gesture {
+    pub colour: u8,
+    pub start: (u8, u8),
+    /// Where the stylus is dragged in straight lines while it's pressed down. Sliding
+    /// back over cells already drawn in the colour doesn't change them, so those drags
+    /// are no different to any other.
+    pub drags: Vec<(u8, u8)>,
+    /// Whether the stylus was dragged onto `start` from the end of the gesture before
+    /// without being lifted
+    pub held: bool,
+}
+
+impl Gesture {
+    /// Where the stylus leaves the colour, either by being lifted or by being dragged
+    /// on into the next gesture
+    pub fn end(&self) -> (u8, u8) {
+        self.drags.last().copied().unwrap_or(self.start)
+    }
+
+    /// The same moves as a stroke, which is all the motors need to draw it. A held
+    /// gesture's stroke doesn't include getting to its start, see [`gesture_strokes`].
+    pub fn stroke(&self) -> Stroke {
+        Stroke {
+            colour: self.colour,
+            vertices: std::iter::once(self.start)
+                .chain(self.drags.iter().copied())
+                .collect(),
+            pen_down: true,
+        }
+    }
+}
+
+/// Strokes making the gestures, where a held gesture's stroke starts from where the
+/// one before ended so the pen isn't lifted in between
+pub fn gesture_strokes(gestures: &[Gesture]) -> Vec<Stroke> {
+    let mut strokes: Vec<Stroke> = Vec::new();
+    for gesture in gestures {
+        let mut stroke = gesture.stroke();
+        if let Some(before) = strokes.last().filter(|_| gesture.held) {
+            stroke
+                .vertices
+                .insert(0, before.vertices[before.vertices.len() - 1]);
+        }
+        strokes.push(stroke);
+    }
+    strokes
+}
+
+/// Gestures drawing every path of the solution, lifting the stylus as few times as
+/// possible, in the same order as [`pathfind`]
+pub fn swipe(board: impl Into<Board>, solved_grid: &Array2D) -> Vec<Gesture> {
+    let board = board.into();
+    let strokes = pathfind(board.clone(), solved_grid);
+    gestures(&board, solved_grid, &strokes)
+}
+
+/// Joins strokes up wherever the stylus can get from the end of one to the start of
+/// the next without lifting, either over the colour it's drawing or, once that's
+/// finished, over it onto the next colour. A stroke that's a whole path can be drawn
+/// the other way round if that's the end the stylus can reach. The strokes with the
+/// pen up are left out, since the stylus is lifted between gestures anyway.
+pub(crate) fn gestures(board: &Board, solved_grid: &Array2D, strokes: &[Stroke]) -> Vec<Gesture> {
+    let strokes: Vec<&Stroke> = (strokes.iter())
+        .filter(|s| s.pen_down && !s.vertices.is_empty())
+        .collect();
+    let mut gestures: Vec<Gesture> = Vec::new();
+    let mut drawn = HashSet::new();
+    let is_end = |at, colour| board.grid[pos(at)] == Cell::Colour(colour);
+
+    for (i, stroke) in strokes.iter().enumerate() {
+        let colour = stroke.colour;
+        let mut vertices = stroke.vertices.clone();
+
+        let slide = gestures.last().and_then(|gesture| {
+            if gesture.colour == colour {
+                let slide = retrace(
+                    board,
+                    solved_grid,
+                    &drawn,
+                    gesture.end(),
+                    vertices[0],
+                    colour,
+                )?;
+                return Some((slide, false));
+            }
+            // nothing more of the colour to draw, so the stylus can leave it
+            if strokes[i..].iter().any(|s| s.colour == gesture.colour) {
+                return None;
+            }
+            let mut ways = vec![vertices.clone()];
+            if is_end(vertices[0], colour) && is_end(vertices[vertices.len() - 1], colour) {
+                ways.push(vertices.iter().rev().copied().collect());
+            }
+            ways.into_iter().find_map(|way| {
+                let path = retrace(
+                    board,
+                    solved_grid,
+                    &drawn,
+                    gesture.end(),
+                    way[0],
+                    gesture.colour,
+                )?;
+                vertices = way;
+                Some((path, true))
+            })
+        });
+        let draw = vertices[1..].iter().copied();
+        match slide {
+            Some((slide, false)) => {
+                let gesture = gestures.last_mut().unwrap();
+                gesture.drags.extend(corners(&slide));
+                gesture.drags.extend(draw);
+            }
+            Some((slide, true)) => {
+                // up to the cell next to the new colour's end, and then onto it
+                let gesture = gestures.last_mut().unwrap();
+                gesture
+                    .drags
+                    .extend(corners(&slide[..slide.len().saturating_sub(1)]));
+                gestures.push(Gesture {
+                    colour,
+                    start: vertices[0],
+                    drags: draw.collect(),
+                    held: true,
+                });
+            }
+            None => gestures.push(Gesture {
+                colour,
+                start: vertices[0],
+                drags: draw.collect(),
+                held: false,
+            }),
+        }
+
+        drawn.insert((pos(vertices[0]), colour));
+        for line in vertices.windows(2) {
+            drawn.extend(
+                straight(board, pos(line[0]), pos(line[1]))
+                    .into_iter()
+                    .map(|cell| (cell, colour)),
+            );
+        }
+    }
+    gestures
+}
+
+fn pos((x, y): (u8, u8)) -> (IndexTy, IndexTy) {
+    (x as IndexTy, y as IndexTy)
+}
+
+/// Moves across the screen from `pos` through `side`, which unlike going through a warp
+/// keeps the stylus on the cells in between
+fn slide(board: &Board, pos: (IndexTy, IndexTy), side: Side) -> Option<(IndexTy, IndexTy)> {
+    if board.is_warp(pos, side) {
+        return None;
+    }
+    board.step(pos, side).map(|(next, _)| next)
+}
+
+/// Cells from `from` to `to` in a straight line, not including `from`
+fn straight(
+    board: &Board,
+    from: (IndexTy, IndexTy),
+    to: (IndexTy, IndexTy),
+) -> Vec<(IndexTy, IndexTy)> {
+    for &side in board.topology().sides() {
+        let mut line = Vec::new();
+        let mut at = from;
+        while let Some(next) = slide(board, at, side) {
+            line.push(next);
+            if next == to {
+                return line;
+            }
+            at = next;
+        }
+    }
+    Vec::new()
+}
+
+/// Whether the stylus can go through the cell in the colour, moving through `side`
+fn carries(cell: Cell, colour: u8, side: Side) -> bool {
+    match cell {
+        Cell::Colour(c) => c == colour,
+        Cell::Bridge {
+            horizontal,
+            vertical,
+        } => {
+            colour
+                == if side.is_horizontal() {
+                    horizontal
+                } else {
+                    vertical
+                }
+        }
+        Cell::Empty | Cell::Wall => false,
+    }
+}
+
+/// The shortest way from `from` to `to` over cells already drawn in `colour`, if there
+/// is one, as each cell after `from` and the side it's come into through. `to` can be
+/// any cell, since that's where the stylus stops.
+fn retrace(
+    board: &Board,
+    solved_grid: &Array2D,
+    drawn: &HashSet<((IndexTy, IndexTy), u8)>,
+    from: (u8, u8),
+    to: (u8, u8),
+    colour: u8,
+) -> Option<Vec<((IndexTy, IndexTy), Side)>> {
+    let (from, to) = (pos(from), pos(to));
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(at) = queue.pop_front() {
+        if at == to {
+            break;
+        }
+        for &side in board.topology().sides() {
+            if !carries(solved_grid[at], colour, side) {
+                continue;
+            }
+            let Some(next) = slide(board, at, side) else {
+                continue;
+            };
+            let usable = next == to
+                || (drawn.contains(&(next, colour)) && carries(solved_grid[next], colour, side));
+            if usable && next != from && !came_from.contains_key(&next) {
+                came_from.insert(next, (at, side));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut at = to;
+    while at != from {
+        let &(prev, side) = came_from.get(&at)?;
+        path.push((at, side));
+        at = prev;
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// The cells where a way found by [`retrace`] turns, and the cell it ends at
+fn corners(path: &[((IndexTy, IndexTy), Side)]) -> Vec<(u8, u8)> {
+    let mut corners: Vec<(u8, u8)> = (path.windows(2))
+        .filter(|step| step[0].1 != step[1].1)
+        .map(|step| cell(step[0].0))
+        .collect();
+    corners.extend(path.last().map(|(at, _)| cell(*at)));
+    corners
+}
@@ -1 +1,458 @@
+use solver::{make_grid_and_pairs, parse_board, parse_grid, solve, validate, Board, Cell, Hex};
 
+use crate::{
+    gesture_strokes,
+    order::{exact, improve, nearest, plan, travel},
+    pathfind, pen_moves, simulate, swipe,
+    swipe::gestures,
+    Gesture, Pen, Problem, Stroke,
+};
+
+#[test]
+fn test_non_square() {
+    #[rustfmt::skip]
+    let points = [
+        1, 0, 0, 0, 0, 1,
+        2, 0, 0, 0, 0, 2,
+    ];
+    let (grid, heads) = make_grid_and_pairs((6, 2), &mut points.into_iter());
+    let result = solve(grid.clone(), heads);
+    assert!(result.is_solved());
+    let solved = result.grid;
+
+    let strokes = pathfind(grid, &solved);
+    assert_eq!(
+        strokes,
+        vec![
+            Stroke {
+                colour: 2,
+                vertices: vec![(5, 1), (0, 1)],
+                pen_down: true,
+            },
+            Stroke {
+                colour: 0,
+                vertices: vec![(0, 1), (0, 0)],
+                pen_down: false,
+            },
+            Stroke {
+                colour: 1,
+                vertices: vec![(0, 0), (5, 0)],
+                pen_down: true,
+            },
+        ]
+    );
+    // the pen only goes up or down where the strokes change between them
+    assert_eq!(
+        pen_moves(&strokes),
+        [
+            Pen::Up,
+            Pen::Goto((5, 1)),
+            Pen::Down,
+            Pen::Goto((0, 1)),
+            Pen::Up,
+            Pen::Goto((0, 0)),
+            Pen::Down,
+            Pen::Goto((5, 0)),
+            Pen::Up,
+        ]
+    );
+}
+
+#[test]
+fn test_bridges_and_warps() {
+    #[rustfmt::skip]
+    let points = [
+        0, 1, 0,
+        2, 0, 2,
+        0, 1, 0,
+    ];
+    let (mut grid, heads) = make_grid_and_pairs((3, 3), &mut points.into_iter());
+    for corner in [(0, 0), (2, 0), (0, 2), (2, 2)] {
+        grid[corner] = Cell::Wall;
+    }
+    grid[(1, 1)] = Cell::Bridge {
+        horizontal: 0,
+        vertical: 0,
+    };
+    let result = solve(grid.clone(), heads);
+    assert!(result.is_solved());
+
+    // starting either path first moves the pen just as far
+    assert_eq!(
+        pathfind(grid, &result.grid),
+        vec![
+            Stroke {
+                colour: 2,
+                vertices: vec![(2, 1), (0, 1)],
+                pen_down: true,
+            },
+            Stroke {
+                colour: 0,
+                vertices: vec![(0, 1), (1, 0)],
+                pen_down: false,
+            },
+            Stroke {
+                colour: 1,
+                vertices: vec![(1, 0), (1, 2)],
+                pen_down: true,
+            },
+        ]
+    );
+
+    let (grid, heads) = make_grid_and_pairs((4, 1), &mut [0, 1, 1, 0].into_iter());
+    let mut board = Board::new(grid);
+    board.wrap_horizontally();
+    let result = solve(board.clone(), heads);
+    assert!(result.is_solved());
+
+    // the pen comes up to go through the warp
+    assert_eq!(
+        pathfind(board, &result.grid),
+        vec![
+            Stroke {
+                colour: 1,
+                vertices: vec![(2, 0), (3, 0)],
+                pen_down: true,
+            },
+            Stroke {
+                colour: 0,
+                vertices: vec![(3, 0), (0, 0)],
+                pen_down: false,
+            },
+            Stroke {
+                colour: 1,
+                vertices: vec![(0, 0), (1, 0)],
+                pen_down: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_hex() {
+    let (grid, heads) = make_grid_and_pairs((2, 2), &mut [1, 0, 0, 1].into_iter());
+    let board = Board::with_topology(grid, &Hex);
+    let result = solve(board.clone(), heads);
+    assert!(result.is_solved());
+
+    assert_eq!(
+        pathfind(board, &result.grid),
+        vec![Stroke {
+            colour: 1,
+            vertices: vec![(1, 1), (0, 1), (1, 0), (0, 0)],
+            pen_down: true,
+        }]
+    );
+}
+
+#[test]
+fn test_path_alongside_itself() {
+    // the only colour fills the board, so every cell has too many neighbours of its
+    // own colour to tell which way the path goes from the grid alone
+    for (board, ends) in [
+        ("1..\n...\n..1", [(0, 0), (2, 2)]),
+        ("1.1\n...\n...", [(0, 0), (2, 0)]),
+    ] {
+        let (grid, _) = parse_board(board).unwrap();
+        let solved = parse_grid("111\n111\n111").unwrap();
+        let strokes = pathfind(grid.clone(), &solved);
+        assert_eq!(strokes.len(), 1);
+        let vertices = &strokes[0].vertices;
+        let mut drawn = [vertices[0], vertices[vertices.len() - 1]];
+        drawn.sort();
+        assert_eq!(drawn, ends);
+        assert!(simulate(grid, &solved, &pen_moves(&strokes)).is_clean());
+    }
+}
+
+#[test]
+fn test_agrees_with_validate() {
+    let cases = [
+        ("1...\n..2.\n.1..\n...2", "1111\n2221\n2111\n2222"),
+        ("1...\n..2.\n.1..\n...2", "1111\n2221\n2111\n1222"),
+        ("1.1\n...\n...", "111\n111\n111"),
+        ("11.\n...\n...", "111\n111\n111"),
+        ("1..\n...\n..1", "111\n111\n111"),
+        ("1.1\n2.2", "121\n212"),
+        ("1.1\n2.2", "111\n222"),
+        ("1..2\n....\n2..1", "1112\n2212\n2211"),
+        ("#1#\n2+2\n#1#", "#1#\n2(21)2\n#1#"),
+        ("#1#\n2+2\n#1#", "#1#\n2(12)2\n#1#"),
+    ];
+    for (board, solution) in cases {
+        let (grid, _) = parse_board(board).unwrap();
+        let solved = parse_grid(solution).unwrap();
+        let strokes = pathfind(grid.clone(), &solved);
+        let drawn = simulate(grid.clone(), &solved, &pen_moves(&strokes));
+        assert_eq!(
+            validate(grid, &solved).is_ok(),
+            drawn.is_clean(),
+            "{board} solved as {solution}"
+        );
+    }
+}
+
+/// Ends of some strokes spread around a 15x15 board
+fn strokes(count: usize) -> Vec<[(f64, f64); 2]> {
+    (0..count)
+        .map(|i| {
+            let a = ((i * 7 % 15) as f64, (i * 11 % 15) as f64);
+            let b = ((i * 4 % 15) as f64, (i * 13 % 15) as f64);
+            [a, b]
+        })
+        .collect()
+}
+
+#[test]
+fn test_exact_order() {
+    fn permutations(
+        left: Vec<usize>,
+        order: &mut Vec<(usize, bool)>,
+        found: &mut Vec<Vec<(usize, bool)>>,
+    ) {
+        if left.is_empty() {
+            found.push(order.clone());
+        }
+        for (i, &stroke) in left.iter().enumerate() {
+            for backwards in [false, true] {
+                let mut rest = left.clone();
+                rest.remove(i);
+                order.push((stroke, backwards));
+                permutations(rest, order, found);
+                order.pop();
+            }
+        }
+    }
+
+    let pen = (14.0, 14.0);
+    for count in 0..6 {
+        let strokes = strokes(count);
+        let mut every = Vec::new();
+        permutations((0..count).collect(), &mut Vec::new(), &mut every);
+        let shortest = every
+            .iter()
+            .map(|order| travel(pen, &strokes, order))
+            .fold(f64::INFINITY, f64::min);
+
+        let order = exact(pen, &strokes);
+        assert_eq!(order.len(), count);
+        assert!((travel(pen, &strokes, &order) - shortest).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_large_order() {
+    let pen = (14.0, 14.0);
+    let strokes = strokes(20);
+    let greedy = nearest(pen, &strokes);
+    let order = plan(pen, &strokes);
+    assert_eq!(order, improve(pen, &strokes, greedy.clone()));
+
+    let mut drawn: Vec<_> = order.iter().map(|s| s.0).collect();
+    drawn.sort();
+    assert_eq!(drawn, (0..20).collect::<Vec<_>>());
+    assert!(travel(pen, &strokes, &order) < travel(pen, &strokes, &greedy));
+
+    // and it isn't far off the best for a smaller set
+    let strokes = &strokes[..12];
+    let best = travel(pen, strokes, &exact(pen, strokes));
+    let good = travel(pen, strokes, &improve(pen, strokes, nearest(pen, strokes)));
+    assert!(good <= best * 1.25, "{good} vs {best}");
+}
+
+#[test]
+fn test_swipe() {
+    // one press, sliding from the end of the first colour onto the second, and
+    // otherwise drawn just like the strokes
+    #[rustfmt::skip]
+    let points = [
+        1, 0, 0, 0, 0, 1,
+        2, 0, 0, 0, 0, 2,
+    ];
+    let (grid, heads) = make_grid_and_pairs((6, 2), &mut points.into_iter());
+    let result = solve(grid.clone(), heads);
+    let planned = swipe(grid.clone(), &result.grid);
+    assert_eq!(
+        planned,
+        vec![
+            Gesture {
+                colour: 2,
+                start: (5, 1),
+                drags: vec![(0, 1)],
+                held: false,
+            },
+            Gesture {
+                colour: 1,
+                start: (0, 0),
+                drags: vec![(5, 0)],
+                held: true,
+            },
+        ]
+    );
+    let strokes: Vec<_> = planned.iter().map(Gesture::stroke).collect();
+    let mut drawn = pathfind(grid.clone(), &result.grid);
+    drawn.retain(|s| s.pen_down);
+    assert_eq!(strokes, drawn);
+    let moves = pen_moves(&gesture_strokes(&planned));
+    assert_eq!(moves.iter().filter(|m| **m == Pen::Up).count(), 2);
+    assert!(simulate(grid, &result.grid, &moves).is_clean());
+
+    // the other side of the warp can't be reached without drawing over (1, 0)
+    let (grid, heads) = make_grid_and_pairs((4, 1), &mut [0, 1, 1, 0].into_iter());
+    let mut board = Board::new(grid);
+    board.wrap_horizontally();
+    let result = solve(board.clone(), heads);
+    assert_eq!(swipe(board, &result.grid).len(), 2);
+
+    // but here it can, by going back along the top row
+    let (solved, _) = make_grid_and_pairs((3, 2), &mut [1, 1, 1, 1, 2, 2].into_iter());
+    let mut board = Board::new(solved.clone());
+    board.wrap_horizontally();
+    let strokes = [
+        Stroke {
+            colour: 1,
+            vertices: vec![(1, 0), (2, 0)],
+            pen_down: true,
+        },
+        Stroke {
+            colour: 1,
+            vertices: vec![(0, 0), (0, 1)],
+            pen_down: true,
+        },
+    ];
+    assert_eq!(
+        gestures(&board, &solved, &strokes),
+        vec![Gesture {
+            colour: 1,
+            start: (1, 0),
+            // sliding back to (0, 0) over what's been drawn
+            drags: vec![(2, 0), (0, 0), (0, 1)],
+            held: false,
+        }]
+    );
+}
+
+#[test]
+fn test_simulate() {
+    #[rustfmt::skip]
+    let points = [
+        1, 0, 0, 0, 0, 1,
+        2, 0, 0, 0, 0, 2,
+    ];
+    let (grid, heads) = make_grid_and_pairs((6, 2), &mut points.into_iter());
+    let solved = solve(grid.clone(), heads).grid;
+    let strokes = pathfind(grid.clone(), &solved);
+    let check = |moves: &[Pen]| simulate(grid.clone(), &solved, moves).problems;
+
+    let simulation = simulate(grid.clone(), &solved, &pen_moves(&strokes));
+    assert!(simulation.is_clean());
+    assert_eq!(simulation.lines.len(), 3);
+    assert_eq!(simulation.lines[1].colour, None);
+
+    // leaving out a stroke misses its cells
+    let problems = check(&pen_moves(&strokes[1..]));
+    assert_eq!(problems.len(), 6);
+    assert!(problems.contains(&Problem::Missed {
+        colour: 2,
+        at: (3, 1)
+    }));
+
+    // dragging the pen from one colour into the other before it's finished
+    let problems = check(&[
+        Pen::Goto((5, 1)),
+        Pen::Down,
+        Pen::Goto((3, 1)),
+        Pen::Goto((3, 0)),
+        Pen::Goto((0, 0)),
+    ]);
+    assert_eq!(
+        problems[..2],
+        [
+            Problem::Overdraw {
+                colour: 2,
+                at: (3, 0)
+            },
+            Problem::Overdraw {
+                colour: 2,
+                at: (2, 0)
+            },
+        ]
+    );
+    assert!(problems.contains(&Problem::Overdraw {
+        colour: 2,
+        at: (0, 0)
+    }));
+
+    // but once it is, dragging onto the end of the other picks that colour up
+    let mut moves = pen_moves(&strokes);
+    moves.retain(|m| *m != Pen::Up);
+    assert!(check(&moves).is_empty());
+
+    // starting halfway along, and going off the edge
+    let problems = check(&[
+        Pen::Goto((2, 0)),
+        Pen::Down,
+        Pen::Goto((0, 0)),
+        Pen::Up,
+        Pen::Goto((6, 0)),
+    ]);
+    assert_eq!(
+        problems[..2],
+        [Problem::OffEnd((2, 0)), Problem::OffGrid((6, 0))]
+    );
+
+    // coming out of a warp carries on the path that went in
+    let (grid, heads) = make_grid_and_pairs((4, 1), &mut [0, 1, 1, 0].into_iter());
+    let mut board = Board::new(grid);
+    board.wrap_horizontally();
+    let solved = solve(board.clone(), heads).grid;
+    let strokes = pathfind(board.clone(), &solved);
+    assert!(simulate(board.clone(), &solved, &pen_moves(&strokes)).is_clean());
+    assert_eq!(
+        simulate(board, &solved, &pen_moves(&strokes[1..])).problems[0],
+        Problem::OffEnd((0, 0))
+    );
+}
+
+#[test]
+fn test_swipe_between_colours() {
+    let lifts = |strokes: &[Stroke]| pen_moves(strokes).iter().filter(|m| **m == Pen::Up).count();
+
+    // each colour slides back over itself to next to an end of the next one, so the
+    // whole board is drawn in one press where the pen would be lifted between each
+    let (grid, heads) = parse_board("1.2.3\n..4.5\n.....\n.2.3.\n.145.").unwrap();
+    let solved = solve(grid.clone(), heads).grid;
+    let planned = swipe(grid.clone(), &solved);
+    assert_eq!(planned.len(), 5);
+    assert!(planned[1..].iter().all(|g| g.held));
+    let strokes = gesture_strokes(&planned);
+    assert_eq!(lifts(&strokes), 2);
+    assert_eq!(lifts(&pathfind(grid.clone(), &solved)), 6);
+    assert!(simulate(grid, &solved, &pen_moves(&strokes)).is_clean());
+
+    // going straight back over a bridge the colour crossed, and drawing the next colour
+    // from its other end
+    let (grid, heads) = parse_board("#.1#\n2+.2\n#1##").unwrap();
+    let solved = solve(grid.clone(), heads).grid;
+    let planned = swipe(grid.clone(), &solved);
+    assert_eq!(
+        planned,
+        vec![
+            Gesture {
+                colour: 2,
+                start: (3, 1),
+                drags: vec![(0, 1), (2, 1)],
+                held: false,
+            },
+            Gesture {
+                colour: 1,
+                start: (2, 0),
+                drags: vec![(1, 0), (1, 2)],
+                held: true,
+            },
+        ]
+    );
+    let strokes = gesture_strokes(&planned);
+    assert_eq!(strokes[1].vertices[0], (2, 1));
+    assert!(simulate(grid, &solved, &pen_moves(&strokes)).is_clean());
+}
@@ -0,0 +1,69 @@
+//! How much the solver allocates on the 14x14 boards, which criterion can't measure. Run
+//! with `cargo bench --bench allocations`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::corpus;
+
+/// Passes everything on to the system allocator, counting as it goes
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(live, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn main() {
+    println!(
+        "{:<20} {:>9} {:>11} {:>13} {:>11} {:>13}",
+        "board", "nodes", "backtracks", "allocations", "MB total", "peak KB"
+    );
+    for kind in ["solvable", "hard"] {
+        for case in corpus(kind)
+            .into_iter()
+            .filter(|c| c.name.starts_with("14x14"))
+        {
+            ALLOCATIONS.store(0, Ordering::Relaxed);
+            BYTES.store(0, Ordering::Relaxed);
+            PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+            let base = LIVE.load(Ordering::Relaxed);
+
+            let result = solver::solve(case.grid, case.heads);
+            assert!(result.is_solved(), "{kind}/{} wasn't solved", case.name);
+
+            println!(
+                "{:<20} {:>9} {:>11} {:>13} {:>11.1} {:>13.1}",
+                format!("{kind}/{}", case.name),
+                result.stats.nodes,
+                result.stats.backtracks,
+                ALLOCATIONS.load(Ordering::Relaxed),
+                BYTES.load(Ordering::Relaxed) as f64 / 1e6,
+                (PEAK.load(Ordering::Relaxed) - base) as f64 / 1e3,
+            );
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::corpus;
+
+fn bench_corpus(c: &mut Criterion, kind: &str) {
+    let mut group = c.benchmark_group(kind);
+    if kind == "hard" {
+        group.sample_size(10);
+    }
+    for case in corpus(kind) {
+        group.bench_with_input(BenchmarkId::from_parameter(&case.name), &case, |b, case| {
+            b.iter(|| solver::solve(case.grid.clone(), case.heads.clone()))
+        });
+    }
+    group.finish();
+}
+
+fn solvable(c: &mut Criterion) {
+    bench_corpus(c, "solvable");
+}
+
+fn impossible(c: &mut Criterion) {
+    bench_corpus(c, "impossible");
+}
+
+fn hard(c: &mut Criterion) {
+    bench_corpus(c, "hard");
+}
+
+criterion_group!(benches, solvable, impossible, hard);
+criterion_main!(benches);
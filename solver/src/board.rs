@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Array2D, Cell, IndexTy, Side, Square, Topology};
+
+type Edge = ((IndexTy, IndexTy), Side);
+
+/// A grid along with how its cells connect. By default each cell connects to the four
+/// next to it, walls between cells cut that and warps link cells that aren't next to
+/// each other at all. Bridges and wrapping only make sense on square boards.
+#[derive(Clone, Debug)]
+pub struct Board {
+    pub grid: Array2D,
+    walls: HashSet<Edge>,
+    warps: HashMap<Edge, Edge>,
+    topology: &'static dyn Topology,
+}
+
+impl Board {
+    pub fn new(grid: Array2D) -> Self {
+        Self::with_topology(grid, &Square)
+    }
+
+    pub fn with_topology(grid: Array2D, topology: &'static dyn Topology) -> Self {
+        Self {
+            grid,
+            walls: HashSet::new(),
+            warps: HashMap::new(),
+            topology,
+        }
+    }
+
+    pub fn topology(&self) -> &'static dyn Topology {
+        self.topology
+    }
+
+    /// Blocks the edge on `side` of `pos`, from both directions
+    pub fn add_wall(&mut self, pos: (IndexTy, IndexTy), side: Side) {
+        self.walls.insert((pos, side));
+        self.walls
+            .insert((self.topology.neighbour(pos, side), side.opposite()));
+    }
+
+    /// Links leaving `a` through one side to entering `b` through another, and the
+    /// other way round
+    pub fn add_warp(&mut self, a: Edge, b: Edge) {
+        self.warps.insert(a, b);
+        self.warps.insert(b, a);
+    }
+
+    /// Joins the left and right edges of the board
+    pub fn wrap_horizontally(&mut self) {
+        let right = self.grid.width() as IndexTy - 1;
+        for y in 0..self.grid.height() as IndexTy {
+            self.add_warp(((0, y), Side::Left), ((right, y), Side::Right));
+        }
+    }
+
+    /// Joins the top and bottom edges of the board
+    pub fn wrap_vertically(&mut self) {
+        let bottom = self.grid.height() as IndexTy - 1;
+        for x in 0..self.grid.width() as IndexTy {
+            self.add_warp(((x, 0), Side::Up), ((x, bottom), Side::Down));
+        }
+    }
+
+    pub fn is_warp(&self, pos: (IndexTy, IndexTy), side: Side) -> bool {
+        self.warps.contains_key(&(pos, side))
+    }
+
+    /// Where leaving `pos` through `side` ends up, and the side it comes in through
+    pub fn step(&self, pos: (IndexTy, IndexTy), side: Side) -> Option<Edge> {
+        if self.walls.contains(&(pos, side)) {
+            return None;
+        }
+        let (next, next_side) = match self.warps.get(&(pos, side)) {
+            Some(&target) => target,
+            None => (self.topology.neighbour(pos, side), side.opposite()),
+        };
+        match self.grid.get(next) {
+            None | Some(Cell::Wall) => None,
+            Some(_) => Some((next, next_side)),
+        }
+    }
+}
+
+impl From<Array2D> for Board {
+    fn from(grid: Array2D) -> Self {
+        Self::new(grid)
+    }
+}
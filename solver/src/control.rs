@@ -0,0 +1,151 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use crate::{
+    search::{Puzzle, State},
+    Array2D, Step,
+};
+
+/// Nodes between calls to [`SolveOptions::progress`]
+const PROGRESS_INTERVAL: usize = 256;
+
+/// Stops a solve from another thread. Clones all stop the same solve.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How far a solve has got, see [`SolveOptions::progress`]
+pub struct Progress<'a> {
+    /// Guesses the search is currently relying on
+    pub depth: usize,
+    pub nodes: usize,
+    /// The board with as many cells filled in as the search has managed so far. It
+    /// doesn't have to lead anywhere.
+    pub best: &'a Array2D,
+}
+
+pub type ProgressFn<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// When to stop a solve early, and how to follow it while it runs. A solve that stops
+/// early is [`crate::SolveStatus::GaveUp`].
+#[derive(Default)]
+pub struct SolveOptions<'a> {
+    /// Board states to look at before giving up
+    pub max_nodes: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelToken>,
+    /// Called every few hundred board states
+    pub progress: Option<ProgressFn<'a>>,
+    /// Where to write down every move, guess and backtrack, see [`crate::explain`].
+    /// This slows the search down a lot on hard boards.
+    pub explain: Option<&'a mut Vec<Step>>,
+    /// Threads to spread the search over, see [`crate::Parallel`]. 0 and 1 both search
+    /// on the calling thread alone, and so does any search with `explain` set.
+    pub threads: usize,
+}
+
+/// Keeps track of [`SolveOptions`] during a search
+pub(crate) struct Control<'o, 'a> {
+    options: &'o mut SolveOptions<'a>,
+    /// The board being solved, only needed for progress reports
+    grid: Option<&'o Array2D>,
+    /// Most filled in state so far, with how many nodes it has filled
+    best: Option<(usize, State)>,
+    /// Nodes at the last progress report
+    reported: Option<usize>,
+    stopped: bool,
+}
+
+impl<'o, 'a> Control<'o, 'a> {
+    pub fn new(options: &'o mut SolveOptions<'a>, grid: Option<&'o Array2D>) -> Self {
+        Self {
+            options,
+            grid,
+            best: None,
+            reported: None,
+            stopped: false,
+        }
+    }
+
+    /// Whether the search stopped because of the options rather than finishing
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Where the search should record what it does, if anywhere
+    pub fn log(&mut self) -> Option<&mut Vec<Step>> {
+        self.options.explain.as_deref_mut()
+    }
+
+    /// Board states to look at before giving up, if there's a limit
+    pub fn max_nodes(&self) -> Option<usize> {
+        self.options.max_nodes
+    }
+
+    /// Whether the search should stop, having looked at `nodes` board states
+    pub fn should_stop(&mut self, nodes: usize) -> bool {
+        let options = &*self.options;
+        self.stopped |= options.max_nodes.is_some_and(|max| nodes >= max)
+            || options
+                .cancel
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+            || options
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+        self.stopped
+    }
+
+    /// Called before every node with how many there have been so far, returns whether
+    /// the search should stop
+    pub fn tick(&mut self, puzzle: &Puzzle, state: &State, depth: usize, nodes: usize) -> bool {
+        if self.should_stop(nodes) {
+            return true;
+        }
+
+        let options = &mut *self.options;
+        let (Some(progress), Some(grid)) = (&mut options.progress, self.grid) else {
+            return false;
+        };
+        let filled = state.colours.iter().filter(|c| **c != 0).count();
+        if self.best.as_ref().is_none_or(|(best, _)| filled > *best) {
+            self.best = Some((filled, state.clone()));
+        }
+        // a parallel search counts other threads' nodes too, so this can skip past
+        // multiples of the interval
+        if self
+            .reported
+            .is_none_or(|reported| nodes >= reported + PROGRESS_INTERVAL)
+        {
+            self.reported = Some(nodes);
+            let mut best = grid.clone();
+            if let Some((_, state)) = &self.best {
+                state.write_to(puzzle, &mut best);
+            }
+            progress(&Progress {
+                depth,
+                nodes,
+                best: &best,
+            });
+        }
+        false
+    }
+}
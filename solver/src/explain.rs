@@ -0,0 +1,79 @@
+//! A record of how the search got to its answer, one [`Step`] at a time, for replaying
+//! a solve or working out why it went wrong. See [`crate::SolveOptions::explain`].
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::IndexTy;
+
+/// Something the search did. Colours are the values written in the grid, and a
+/// `cell` of `None` means the colour's two ends joined up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Step {
+    /// A move that had to be made
+    Forced {
+        colour: u8,
+        cell: Option<(IndexTy, IndexTy)>,
+        rule: Rule,
+    },
+    /// A move picked out of `options` possible ones, because nothing was forced
+    Guess {
+        colour: u8,
+        cell: Option<(IndexTy, IndexTy)>,
+        options: usize,
+    },
+    /// The board can't be finished from here
+    Contradiction {
+        cell: (IndexTy, IndexTy),
+        reason: Reason,
+    },
+    /// Back to the board as it was before the guess at `depth`, to try the next option
+    Backtrack {
+        depth: usize,
+    },
+    /// The search started over with a different order of guesses
+    Restart,
+    Solved,
+}
+
+/// Why a move was forced
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Rule {
+    /// The end of the path had only one free neighbour
+    OnlyMove,
+    /// An empty cell with only two ways in or out, like a corner, has to be filled
+    /// from the path next to it
+    Corner,
+    /// Every other way the end of the path could go is a cell another colour can't get
+    /// to its other end without
+    Bottleneck,
+}
+
+/// Why a board can't be finished
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Reason {
+    /// The end of a path has nowhere to go
+    Stuck,
+    /// An empty cell can't have a path go through it
+    DeadEnd,
+    /// Every path is done but cells are still empty
+    Unfilled,
+    /// A colour can no longer reach its other end
+    Unreachable,
+    /// A group of empty cells that no colour can fill
+    Unfillable,
+    /// A group of empty cells with the wrong mix of chessboard colours for the paths
+    /// that can fill it
+    Parity,
+    /// Two colours both have to go through the same cell
+    Bottleneck,
+}
+
+/// The steps as a JSON array
+#[cfg(feature = "serde")]
+pub fn to_json(steps: &[Step]) -> String {
+    serde_json::to_string(steps).expect("steps are always valid JSON")
+}
@@ -0,0 +1,198 @@
+use rand::Rng;
+
+use crate::{make_grid_and_pairs, Array2D, Board, Heads, IndexTy, Sat, Solver};
+
+/// How many random boards are tried before giving up on finding a unique one
+const ATTEMPTS: usize = 200;
+
+type Piece = Vec<(IndexTy, IndexTy)>;
+
+/// Makes a random square board with `colours` pairs which has exactly one solution,
+/// or `None` if none turned up. Colours are numbered as [`make_grid_and_pairs`] does.
+///
+/// Boards are made by winding a single path through every cell, cutting it into
+/// pieces that don't run next to themselves and joining or splitting those until there
+/// is one for each colour. Only the ends of each piece are kept, so there is always at
+/// least one solution, but some boards still leave room for paths to be rerouted and
+/// those are thrown away.
+pub fn generate(
+    size: (usize, usize),
+    colours: usize,
+    rng: &mut impl Rng,
+) -> Option<(Array2D, Heads)> {
+    let cells = size.0 * size.1;
+    if colours == 0 || colours * 2 > cells || colours > u8::MAX as usize {
+        return None;
+    }
+
+    for attempt in 0..ATTEMPTS {
+        // well shuffled paths make for more interesting boards but get cut into lots of
+        // pieces, so they're shuffled less each time there are too many
+        let shuffles = (cells * 20) >> (attempt / 25);
+        let path = random_path(size, shuffles, rng);
+        let Some(pieces) = split_path(&path, colours, rng) else {
+            continue;
+        };
+
+        let mut points = vec![0; cells];
+        for (colour, piece) in pieces.iter().enumerate() {
+            for (x, y) in [piece[0], piece[piece.len() - 1]] {
+                points[x as usize + y as usize * size.0] = colour as u8 + 1;
+            }
+        }
+
+        let (grid, heads) = make_grid_and_pairs(size, &mut points.into_iter());
+        if Sat.count_solutions(Board::new(grid.clone()), heads.clone(), 2) == 1 {
+            return Some((grid, heads));
+        }
+    }
+    None
+}
+
+/// A path visiting every cell once, shuffled `shuffles` times by repeatedly joining one end to a cell
+/// next to it and reversing the loop that makes
+fn random_path(
+    size: (usize, usize),
+    shuffles: usize,
+    rng: &mut impl Rng,
+) -> Vec<(IndexTy, IndexTy)> {
+    let (width, height) = (size.0 as IndexTy, size.1 as IndexTy);
+    // start off snaking back and forth along the rows
+    let mut path: Vec<_> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (if y % 2 == 0 { x } else { width - 1 - x }, y)))
+        .collect();
+    let grid = Array2D::new(size.0, size.1);
+
+    for _ in 0..shuffles {
+        if rng.gen_bool(0.5) {
+            path.reverse();
+        }
+        let end = *path.last().unwrap();
+        let around: Vec<_> = grid.neighbours(end).collect();
+        if around.is_empty() {
+            break;
+        }
+        let next = around[rng.gen_range(0..around.len())];
+        let i = path.iter().position(|p| *p == next).unwrap();
+        path[i + 1..].reverse();
+    }
+    path
+}
+
+fn touching(a: (IndexTy, IndexTy), b: (IndexTy, IndexTy)) -> bool {
+    (a.0 - b.0).abs() + (a.1 - b.1).abs() == 1
+}
+
+/// Whether the piece runs next to itself anywhere, which nearly always lets it take a
+/// shortcut and leave another path to fill in the gap
+fn touches_itself(piece: &[(IndexTy, IndexTy)]) -> bool {
+    piece
+        .iter()
+        .enumerate()
+        .any(|(i, a)| piece.iter().skip(i + 2).any(|b| touching(*a, *b)))
+}
+
+/// Splits the path into `colours` pieces, none of which touch themselves
+fn split_path(
+    path: &[(IndexTy, IndexTy)],
+    colours: usize,
+    rng: &mut impl Rng,
+) -> Option<Vec<Piece>> {
+    // cut the path only where it has to be
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut start = 0;
+    for end in 1..path.len() {
+        if path[start..end - 1].iter().any(|p| touching(*p, path[end])) {
+            pieces.push(path[start..end].to_vec());
+            start = end;
+        }
+    }
+    pieces.push(path[start..].to_vec());
+
+    // join up pieces whose ends meet until there are few enough
+    while pieces.len() > colours {
+        // each piece can be joined on at either end
+        let mut joins = Vec::new();
+        for i in 0..pieces.len() {
+            for j in i + 1..pieces.len() {
+                for (flip_i, flip_j) in [(false, false), (false, true), (true, false), (true, true)]
+                {
+                    let end = if flip_i {
+                        pieces[i][0]
+                    } else {
+                        *pieces[i].last().unwrap()
+                    };
+                    let start = if flip_j {
+                        *pieces[j].last().unwrap()
+                    } else {
+                        pieces[j][0]
+                    };
+                    if touching(end, start) {
+                        joins.push((i, j, flip_i, flip_j));
+                    }
+                }
+            }
+        }
+
+        let joined = loop {
+            if joins.is_empty() {
+                return None;
+            }
+            let (i, j, flip_i, flip_j) = joins.swap_remove(rng.gen_range(0..joins.len()));
+            let mut joined = pieces[i].clone();
+            if flip_i {
+                joined.reverse();
+            }
+            if flip_j {
+                joined.extend(pieces[j].iter().rev());
+            } else {
+                joined.extend(&pieces[j]);
+            }
+            if !touches_itself(&joined) {
+                break (i, j, joined);
+            }
+        };
+        let (i, j, joined) = joined;
+        pieces[i] = joined;
+        pieces.swap_remove(j);
+    }
+
+    // then cut up the long pieces if there are too few
+    let min = if colours * 3 <= path.len() { 3 } else { 2 };
+    while pieces.len() < colours {
+        let long: Vec<_> = (0..pieces.len())
+            .filter(|i| pieces[*i].len() >= min * 2)
+            .collect();
+        if long.is_empty() {
+            return None;
+        }
+        let i = long[rng.gen_range(0..long.len())];
+        let cut = rng.gen_range(min..=pieces[i].len() - min);
+        let rest = pieces[i].split_off(cut);
+        pieces.push(rest);
+    }
+
+    pieces
+        .iter()
+        .all(|piece| piece.len() >= 2)
+        .then_some(pieces)
+}
+
+#[test]
+fn test_generate() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for (size, colours) in [((5, 5), 4), ((7, 6), 6), ((9, 9), 8)] {
+        let (grid, heads) = generate(size, colours, &mut rng).unwrap();
+        assert_eq!((grid.width(), grid.height()), size);
+        assert_eq!(heads.len(), colours);
+
+        let result = crate::solve(grid.clone(), heads.clone());
+        assert!(result.is_solved());
+        assert!(!result.grid.contains_empty());
+        assert_eq!(crate::count_solutions(grid, heads, 2), 1);
+    }
+
+    assert!(generate((2, 2), 3, &mut rng).is_none());
+}
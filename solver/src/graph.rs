@@ -0,0 +1,124 @@
+use crate::{Array2D, Board, Cell, IndexTy, Side};
+
+/// Which part of its cell a node stands for. Bridges are split in two so the paths
+/// crossing them can't turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Layer {
+    Whole,
+    Horizontal,
+    Vertical,
+}
+
+/// The board flattened into a graph of cells, so the search never has to think
+/// about coordinates, bounds, walls or warps
+pub(crate) struct Graph {
+    pub positions: Vec<(IndexTy, IndexTy)>,
+    pub layers: Vec<Layer>,
+    pub adjacent: Vec<Vec<usize>>,
+    /// First node of every cell in reading order, bridges have their vertical node
+    /// straight after
+    nodes: Vec<Option<usize>>,
+    width: usize,
+    height: usize,
+}
+
+impl Graph {
+    pub fn from_board(board: &Board) -> Self {
+        let grid = &board.grid;
+        let mut graph = Self {
+            positions: Vec::with_capacity(grid.width() * grid.height()),
+            layers: Vec::with_capacity(grid.width() * grid.height()),
+            adjacent: Vec::with_capacity(grid.width() * grid.height()),
+            nodes: Vec::with_capacity(grid.width() * grid.height()),
+            width: grid.width(),
+            height: grid.height(),
+        };
+
+        for (pos, cell) in grid.cells() {
+            let layers: &[Layer] = match cell {
+                Cell::Wall => &[],
+                Cell::Bridge { .. } => &[Layer::Horizontal, Layer::Vertical],
+                _ => &[Layer::Whole],
+            };
+            graph
+                .nodes
+                .push((!layers.is_empty()).then_some(graph.positions.len()));
+            for &layer in layers {
+                graph.positions.push(pos);
+                graph.layers.push(layer);
+            }
+        }
+
+        for node in 0..graph.positions.len() {
+            let pos = graph.positions[node];
+            let mut around = Vec::with_capacity(board.topology().sides().len());
+            let nodes = board
+                .topology()
+                .sides()
+                .iter()
+                .copied()
+                .filter(|side| match graph.layers[node] {
+                    Layer::Whole => true,
+                    Layer::Horizontal => side.is_horizontal(),
+                    Layer::Vertical => !side.is_horizontal(),
+                })
+                .filter_map(|side| board.step(pos, side))
+                .filter_map(|(next, side)| graph.node_at(next, side))
+                .filter(|n| *n != node);
+            // warps on narrow boards can reach the same cell two ways
+            for n in nodes {
+                if !around.contains(&n) {
+                    around.push(n);
+                }
+            }
+            graph.adjacent.push(around);
+        }
+
+        graph
+    }
+
+    /// Node for a cell that isn't split in two
+    pub fn index_of(&self, pos: (IndexTy, IndexTy)) -> Option<usize> {
+        self.first_node(pos)
+            .filter(|n| self.layers[*n] == Layer::Whole)
+    }
+
+    /// Node a path entering `pos` through `side` goes into
+    fn node_at(&self, pos: (IndexTy, IndexTy), side: Side) -> Option<usize> {
+        let first = self.first_node(pos)?;
+        match self.layers[first] {
+            Layer::Horizontal if !side.is_horizontal() => Some(first + 1),
+            _ => Some(first),
+        }
+    }
+
+    fn first_node(&self, pos: (IndexTy, IndexTy)) -> Option<usize> {
+        if pos.0 < 0
+            || pos.1 < 0
+            || pos.0 >= self.width as IndexTy
+            || pos.1 >= self.height as IndexTy
+        {
+            return None;
+        }
+        self.nodes[pos.0 as usize + pos.1 as usize * self.width]
+    }
+
+    /// Colour of a node in the grid, 0 if it's empty
+    pub fn read(&self, grid: &Array2D, node: usize) -> u8 {
+        match (grid[self.positions[node]], self.layers[node]) {
+            (Cell::Colour(colour), _) => colour,
+            (Cell::Bridge { horizontal, .. }, Layer::Horizontal) => horizontal,
+            (Cell::Bridge { vertical, .. }, Layer::Vertical) => vertical,
+            _ => 0,
+        }
+    }
+
+    pub fn write(&self, grid: &mut Array2D, node: usize, colour: u8) {
+        let cell = &mut grid[self.positions[node]];
+        match (cell, self.layers[node]) {
+            (Cell::Bridge { horizontal, .. }, Layer::Horizontal) => *horizontal = colour,
+            (Cell::Bridge { vertical, .. }, Layer::Vertical) => *vertical = colour,
+            (cell, _) => *cell = Cell::Colour(colour),
+        }
+    }
+}
@@ -1,20 +1,42 @@
+use std::{collections::HashSet, ops::ControlFlow, time::Instant};
+
+pub use board::Board;
+use control::Control;
+pub use control::{CancelToken, Progress, SolveOptions};
+#[cfg(feature = "serde")]
+pub use explain::to_json;
+pub use explain::{Reason, Rule, Step};
+pub use generate::generate;
 pub use log::trace;
-pub use utils::{get_around, Array2D, IndexTy};
+pub use parallel::Parallel;
+pub use result::{Malformed, SolveResult, SolveStatus, Stats, ThreadStats};
+pub use sat::Sat;
+use search::{Outcome, Puzzle};
+pub use text::{parse_board, parse_grid, ParseError};
+pub use topology::{Hex, Side, Square, Topology};
+pub use utils::{get_around, Array2D, Cell, Grid, IndexTy};
+pub use validate::{trace_paths, validate, Path, Violation};
 
+mod board;
+mod control;
+mod explain;
+mod generate;
+mod graph;
+mod parallel;
+mod result;
+mod sat;
+mod search;
+mod text;
+mod topology;
 mod utils;
+mod validate;
 pub type Heads = Vec<((IndexTy, IndexTy), (IndexTy, IndexTy))>;
 
-const MAX_SOLVE_ITER: usize = 100;
-
-#[derive(Debug)]
-enum StepResult {
-    ProgressMade,
-    Complete,
-    Impossible,
-
-    NoProgress(Vec<(IndexTy, IndexTy)>),
-}
-
+/// Builds a board from its cells in reading order, with 0 for empty cells.
+///
+/// Pairs are listed in the order their colours first appear. A colour with only one
+/// endpoint is paired with `(-1, -1)`, and any endpoints past the second are left in
+/// the grid without a pair - both are reported by [`solve`] as [`Malformed`].
 pub fn make_grid_and_pairs(
     size: (usize, usize),
     points: &mut impl Iterator<Item = u8>,
@@ -24,13 +46,17 @@ pub fn make_grid_and_pairs(
 
     let mut x = 0;
     let mut y = 0;
-    for (i, point) in points.enumerate() {
+    for point in points {
         if point > 0 {
-            grid.set_abs(i, point);
-            match heads.get_mut(point as usize - 1) {
-                Some(pair) => {
+            grid[(x, y)] = Cell::Colour(point);
+            match heads
+                .iter_mut()
+                .find(|(a, _)| grid[*a] == Cell::Colour(point))
+            {
+                Some(pair) if pair.1 == (-1, -1) => {
                     pair.1 = (x, y);
                 }
+                Some(_) => (),
                 None => {
                     heads.push(((x, y), (-1, -1)));
                 }
@@ -47,113 +73,164 @@ pub fn make_grid_and_pairs(
     (grid, heads)
 }
 
-fn next_step(
-    current_head: &mut (IndexTy, IndexTy),
-    paired_head: &mut (IndexTy, IndexTy),
-    grid: &mut Array2D,
-) -> StepResult {
-    let mut all_possibles_list = Vec::with_capacity(4);
+/// Solves the board, see [`SolveStatus`] for the ways this can fail. Takes either a
+/// plain grid or a [`Board`] with walls and warps.
+///
+/// The search is exhaustive, so [`SolveStatus::Impossible`] means the board has no
+/// solution at all
+pub fn solve(board: impl Into<Board>, heads: Heads) -> SolveResult {
+    solve_with_limit(board, heads, usize::MAX)
+}
 
-    for pos in get_around(*current_head) {
-        if pos == *paired_head {
-            return StepResult::Complete;
-        }
-        if grid[pos] == 0 {
-            all_possibles_list.push(pos);
-        }
-    }
+/// Like [`solve`], but gives up once `max_nodes` board states have been looked at
+pub fn solve_with_limit(board: impl Into<Board>, heads: Heads, max_nodes: usize) -> SolveResult {
+    let options = SolveOptions {
+        max_nodes: Some(max_nodes),
+        ..Default::default()
+    };
+    solve_with(board, heads, options)
+}
 
-    match all_possibles_list.len() {
-        0 => StepResult::Impossible,
-        1 => {
-            let only_option = all_possibles_list[0];
-            grid[only_option] = grid[*current_head];
-            *current_head = only_option;
-            return StepResult::ProgressMade;
+/// Like [`solve`], but can be stopped early and reports on how it's going, see
+/// [`SolveOptions`]
+pub fn solve_with(board: impl Into<Board>, heads: Heads, mut options: SolveOptions) -> SolveResult {
+    let start = Instant::now();
+    let board = board.into();
+    let mut stats = Stats::default();
+
+    let puzzle = Puzzle::new(&board, &heads);
+    let mut grid = board.grid.clone();
+    let threads = match options.explain {
+        Some(_) => 1,
+        None => options.threads,
+    };
+    let mut control = Control::new(&mut options, Some(&board.grid));
+    let status = match puzzle {
+        Err(reason) => {
+            trace!("Malformed board: {reason}");
+            SolveStatus::Malformed(reason)
         }
-        _ => StepResult::NoProgress(all_possibles_list),
+        Ok((puzzle, state)) => match if threads > 1 {
+            parallel::search(&puzzle, state, &mut control, &mut stats, threads)
+        } else {
+            search::search(&puzzle, state, &mut control, &mut stats)
+        } {
+            Outcome::Solved(state) => {
+                state.write_to(&puzzle, &mut grid);
+                trace!("Grid:\n{grid}");
+                SolveStatus::Solved
+            }
+            Outcome::Impossible(node) => {
+                trace!("Early exit - grid impossible");
+                SolveStatus::Impossible {
+                    cell: node.map(|n| puzzle.graph.positions[n]),
+                }
+            }
+            Outcome::GaveUp => {
+                trace!("Giving up after {} nodes", stats.nodes);
+                SolveStatus::GaveUp
+            }
+        },
+    };
+
+    stats.elapsed = start.elapsed();
+    SolveResult {
+        grid,
+        status,
+        stats,
     }
 }
 
-pub fn solve(mut grid: Array2D, mut heads: Heads) -> (Array2D, bool) {
-    let mut max_iter_count = 0;
-    let mut undo_stack: Vec<(Array2D, Heads, Vec<(IndexTy, IndexTy)>, u8, usize, usize)> =
-        Vec::new();
-    loop {
-        max_iter_count += 1;
-        if max_iter_count >= MAX_SOLVE_ITER {
-            return (grid, false);
+/// Solves the board while writing down every step along the way, for replaying how it
+/// was solved. See [`Step`].
+pub fn explain(board: impl Into<Board>, heads: Heads) -> (SolveResult, Vec<Step>) {
+    let mut steps = Vec::new();
+    let options = SolveOptions {
+        explain: Some(&mut steps),
+        ..Default::default()
+    };
+    let result = solve_with(board, heads, options);
+    (result, steps)
+}
+
+/// Finds up to `limit` different solutions to the board, or all of them if there are
+/// fewer. Published boards have exactly one, so any other count usually means the
+/// board was misread.
+pub fn solutions(board: impl Into<Board>, heads: Heads, limit: usize) -> Vec<Array2D> {
+    solutions_with(board, heads, limit, SolveOptions::default()).expect("nothing to stop it early")
+}
+
+/// Like [`solutions`], but can be stopped early by the cancel token, deadline or node
+/// limit in `options`. Stopping early gives `None`, as there could have been more
+/// solutions still to find.
+pub fn solutions_with(
+    board: impl Into<Board>,
+    heads: Heads,
+    limit: usize,
+    mut options: SolveOptions,
+) -> Option<Vec<Array2D>> {
+    let board = board.into();
+    let mut found = Vec::new();
+    let Ok((puzzle, state)) = Puzzle::new(&board, &heads) else {
+        trace!("Malformed board");
+        return Some(found);
+    };
+    if limit == 0 {
+        return Some(found);
+    }
+
+    let mut seen = HashSet::new();
+    let mut control = Control::new(&mut options, None);
+    let finished = search::enumerate(&puzzle, state, &mut control, &mut |state| {
+        if seen.insert(state.colours.clone()) {
+            let mut solution = board.grid.clone();
+            state.write_to(&puzzle, &mut solution);
+            found.push(solution);
         }
-        
-        let mut progress_made = false;
-        let mut complete_count = 0;
-        let mut impossible = false;
-        let mut guessing_target = None;
-
-        for (i, (h1, h2)) in heads.iter_mut().enumerate() {
-            for (j, result) in [next_step(h1, h2, &mut grid), next_step(h2, h1, &mut grid)]
-                .into_iter()
-                .enumerate()
-            {
-                match result {
-                    StepResult::ProgressMade => progress_made = true,
-                    StepResult::Complete => complete_count += 1,
-                    StepResult::Impossible => {
-                        impossible = true;
-                        break;
-                    }
-                    StepResult::NoProgress(data) => guessing_target = Some((data, grid[*h1], i, j)),
-                }
-            }
+        if found.len() < limit {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
         }
+    });
 
-        trace!("Complete head count: {complete_count}");
-        if complete_count >= heads.len() * 2 {
-            trace!("Solving iter - complete!");
-            // grid is complete!
-            return (grid, true);
-        }
+    finished.then_some(found)
+}
 
-        if impossible {
-            trace!("Impossible grid found");
-            if let Some((base_grid, base_heads, split, col, i, j)) = undo_stack.last_mut() {
-                if let Some(next_split) = split.pop() {
-                    grid.clone_from(base_grid);
-                    heads.clone_from(base_heads);
-
-                    grid[next_split] = *col;
-                    if *j == 0 {
-                        heads[*i].0 = next_split;
-                    } else {
-                        heads[*i].1 = next_split;
-                    }
-                    trace!("Reverting grid and trying next");
-                } else {
-                    trace!("No guesses made were correct - undoing guess");
-                    undo_stack.pop();
-                }
-            } else {
-                trace!("Early exit - base grid impossible");
-                return (grid, false);
-            }
-        } else if !progress_made {
-            trace!("No progress made");
-            let (mut split, col, i, j) = guessing_target.unwrap(); // should never fail...
-            trace!("Options are: {:?}", split);
-            let next_step = split.pop().unwrap();
-
-            assert!(split.len() != 0);
-            undo_stack.push((grid.clone(), heads.clone(), split, col, i, j));
-
-            grid[next_step] = col;
-            if j == 0 {
-                heads[i].0 = next_step;
-            } else {
-                heads[i].1 = next_step;
-            }
-        }
+/// Counts the solutions to the board, stopping once there are `limit` of them
+pub fn count_solutions(board: impl Into<Board>, heads: Heads, limit: usize) -> usize {
+    solutions(board, heads, limit).len()
+}
 
-        trace!("Grid:\n{grid}");
+/// A way of solving boards, so different approaches can be swapped in and compared
+pub trait Solver {
+    /// Solves the board, see [`SolveStatus`] for the ways this can fail
+    fn solve(&self, board: Board, heads: Heads) -> SolveResult {
+        self.solve_with(board, heads, SolveOptions::default())
+    }
+
+    /// Like [`Solver::solve`], but can be stopped early, see [`SolveOptions`]
+    fn solve_with(&self, board: Board, heads: Heads, options: SolveOptions) -> SolveResult;
+
+    /// Finds up to `limit` different solutions, see [`solutions`]
+    fn solutions(&self, board: Board, heads: Heads, limit: usize) -> Vec<Array2D>;
+
+    /// Counts the solutions to the board, stopping once there are `limit` of them
+    fn count_solutions(&self, board: Board, heads: Heads, limit: usize) -> usize {
+        self.solutions(board, heads, limit).len()
+    }
+}
+
+/// The depth first search behind [`solve`]
+pub struct Backtracking;
+
+impl Solver for Backtracking {
+    fn solve_with(&self, board: Board, heads: Heads, options: SolveOptions) -> SolveResult {
+        solve_with(board, heads, options)
+    }
+
+    fn solutions(&self, board: Board, heads: Heads, limit: usize) -> Vec<Array2D> {
+        solutions(board, heads, limit)
     }
 }
 
@@ -167,11 +244,419 @@ fn test() {
     ];
     let mut grid = Array2D::new(5, 5);
     for (h, (a, b)) in heads.iter().enumerate() {
-        grid[*a] = h as u8 + 1;
-        grid[*b] = h as u8 + 1;
+        grid[*a] = Cell::Colour(h as u8 + 1);
+        grid[*b] = Cell::Colour(h as u8 + 1);
     }
 
-    let grid = solve(grid, heads).0;
+    let grid = solve(grid, heads).grid;
+
+    assert!(!grid.contains_empty());
+}
+
+#[test]
+fn test_non_square() {
+    #[rustfmt::skip]
+    let points = [
+        1, 0, 0, 0, 0, 0, 1,
+        2, 0, 0, 0, 0, 0, 2,
+        3, 0, 0, 0, 0, 0, 3,
+    ];
+    let (grid, heads) = make_grid_and_pairs((7, 3), &mut points.into_iter());
+    assert_eq!((grid.width(), grid.height()), (7, 3));
+    assert_eq!(
+        heads,
+        vec![((0, 0), (6, 0)), ((0, 1), (6, 1)), ((0, 2), (6, 2))]
+    );
+
+    let result = solve(grid, heads);
+
+    assert_eq!(result.status, SolveStatus::Solved);
+    assert!(!result.grid.contains_empty());
+}
+
+#[test]
+fn test_large() {
+    #[rustfmt::skip]
+    let points = [
+        0, 0, 0, 0, 1, 2, 0, 0, 0, 2,
+        3, 0, 0, 0, 0, 0, 4, 5, 0, 0,
+        6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 7, 4, 0, 8, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 5, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 8,
+        0, 0, 0, 3, 1, 0, 9, 7, 0, 10,
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 6, 10, 0, 0, 0, 0,
+    ];
+    let (grid, heads) = make_grid_and_pairs((10, 10), &mut points.into_iter());
+
+    let result = solve(grid, heads);
+
+    assert_eq!(result.status, SolveStatus::Solved);
+    assert!(!result.grid.contains_empty());
+}
+
+#[test]
+fn test_impossible() {
+    #[rustfmt::skip]
+    let points = [
+        1, 0, 2,
+        0, 0, 0,
+        2, 0, 1,
+    ];
+    let (grid, heads) = make_grid_and_pairs((3, 3), &mut points.into_iter());
+
+    let result = solve(grid, heads);
+
+    assert!(matches!(
+        result.status,
+        SolveStatus::Impossible { cell: Some(_) }
+    ));
+    assert!(result.stats.nodes > 0);
+}
+
+#[test]
+fn test_malformed() {
+    let board = |points: [u8; 9]| make_grid_and_pairs((3, 3), &mut points.into_iter());
+
+    // colours don't have to first appear in order
+    let (grid, heads) = board([2, 0, 0, 0, 0, 1, 2, 0, 1]);
+    assert_eq!(heads, vec![((0, 0), (0, 2)), ((2, 1), (2, 2))]);
+    assert!(solve(grid, heads).is_solved());
+
+    let (grid, heads) = board([1, 0, 2, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(
+        solve(grid, heads).status,
+        SolveStatus::Malformed(Malformed::MissingEndpoint {
+            colour: 2,
+            at: (2, 0)
+        })
+    );
 
-    assert!(!grid.contains_zeroes());
+    let (grid, heads) = board([1, 0, 1, 0, 0, 0, 0, 1, 0]);
+    assert_eq!(
+        solve(grid, heads).status,
+        SolveStatus::Malformed(Malformed::StrayCell((1, 2)))
+    );
+}
+
+#[test]
+fn test_gave_up() {
+    #[rustfmt::skip]
+    let points = [
+        0, 0, 0, 0, 1, 2, 0, 0, 0, 2,
+        3, 0, 0, 0, 0, 0, 4, 5, 0, 0,
+        6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 7, 4, 0, 8, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 5, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 8,
+        0, 0, 0, 3, 1, 0, 9, 7, 0, 10,
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 6, 10, 0, 0, 0, 0,
+    ];
+    let (grid, heads) = make_grid_and_pairs((10, 10), &mut points.into_iter());
+
+    let result = solve_with_limit(grid, heads, 1);
+
+    assert_eq!(result.status, SolveStatus::GaveUp);
+    assert_eq!(result.stats.nodes, 1);
+}
+
+#[test]
+fn test_cancel() {
+    #[rustfmt::skip]
+    let points = [
+        0, 0, 0, 0, 1, 2, 0, 0, 0, 2,
+        3, 0, 0, 0, 0, 0, 4, 5, 0, 0,
+        6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 7, 4, 0, 8, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 5, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 8,
+        0, 0, 0, 3, 1, 0, 9, 7, 0, 10,
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 6, 10, 0, 0, 0, 0,
+    ];
+    let (grid, heads) = make_grid_and_pairs((10, 10), &mut points.into_iter());
+
+    let options = SolveOptions {
+        deadline: Some(Instant::now()),
+        ..Default::default()
+    };
+    let result = solve_with(grid.clone(), heads.clone(), options);
+    assert_eq!(result.status, SolveStatus::GaveUp);
+    assert_eq!(result.stats.nodes, 0);
+
+    // stop from the first progress report, which comes before anything is searched
+    let cancel = CancelToken::new();
+    let mut reports = 0;
+    let options = SolveOptions {
+        cancel: Some(cancel.clone()),
+        progress: Some(Box::new(|progress: &Progress| {
+            assert_eq!(progress.best, &grid);
+            reports += 1;
+            cancel.cancel();
+        })),
+        ..Default::default()
+    };
+    let result = solve_with(grid.clone(), heads.clone(), options);
+    assert_eq!(result.status, SolveStatus::GaveUp);
+    assert_eq!(reports, 1);
+
+    // every solver follows the same options
+    for solver in [&Backtracking as &dyn Solver, &Sat, &Parallel { threads: 2 }] {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let options = SolveOptions {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        let result = solver.solve_with(grid.clone().into(), heads.clone(), options);
+        assert_eq!(result.status, SolveStatus::GaveUp);
+
+        let options = SolveOptions {
+            deadline: Some(Instant::now()),
+            ..Default::default()
+        };
+        let result = solver.solve_with(grid.clone().into(), heads.clone(), options);
+        assert_eq!(result.status, SolveStatus::GaveUp);
+    }
+}
+
+#[test]
+fn test_explain() {
+    #[rustfmt::skip]
+    let points = [
+        0, 0, 0, 0, 1, 2, 0, 0, 0, 2,
+        3, 0, 0, 0, 0, 0, 4, 5, 0, 0,
+        6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 7, 4, 0, 8, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 5, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 8,
+        0, 0, 0, 3, 1, 0, 9, 7, 0, 10,
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 6, 10, 0, 0, 0, 0,
+    ];
+    let (grid, heads) = make_grid_and_pairs((10, 10), &mut points.into_iter());
+
+    let (result, steps) = explain(grid, heads);
+    assert!(result.is_solved());
+    assert_eq!(steps.last(), Some(&Step::Solved));
+    assert!(steps.iter().any(|s| matches!(s, Step::Guess { .. })));
+    assert!(steps
+        .iter()
+        .any(|s| matches!(s, Step::Contradiction { .. })));
+    for rule in [Rule::OnlyMove, Rule::Corner] {
+        assert!(steps
+            .iter()
+            .any(|s| matches!(s, Step::Forced { rule: r, .. } if *r == rule)));
+    }
+
+    // the moves after the last restart or backtrack are the ones that solved it
+    let last_try = steps
+        .iter()
+        .rposition(|s| matches!(s, Step::Restart | Step::Backtrack { .. }))
+        .map_or(0, |i| i + 1);
+    for step in &steps[last_try..] {
+        if let Step::Forced {
+            colour,
+            cell: Some(cell),
+            ..
+        } = step
+        {
+            assert_eq!(result.grid[*cell], Cell::Colour(*colour));
+        }
+    }
+
+    // the 3 at (1, 1) can't go up or left, where the 4s have to pass to meet, so it goes
+    // down; colours are numbered in the order they're first read, so 3 is colour 1
+    let (grid, heads) = parse_board(".....\n.31.4\n..2.2\n...1.\n43...\n").unwrap();
+    let (result, bottlenecked) = explain(grid.clone(), heads);
+    assert!(result.is_solved());
+    assert_eq!(validate(grid, &result.grid), Ok(()));
+    assert!(bottlenecked.contains(&Step::Forced {
+        colour: 1,
+        cell: Some((1, 2)),
+        rule: Rule::Bottleneck
+    }));
+
+    #[cfg(feature = "serde")]
+    {
+        let json = to_json(&steps);
+        assert!(json.ends_with(r#""Solved"]"#));
+        let read: Vec<Step> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, steps);
+    }
+}
+
+#[test]
+fn test_backends_agree() {
+    #[rustfmt::skip]
+    let points = [
+        0, 0, 0, 0, 1, 2, 0, 0, 0, 2,
+        3, 0, 0, 0, 0, 0, 4, 5, 0, 0,
+        6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 7, 4, 0, 8, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 5, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 8,
+        0, 0, 0, 3, 1, 0, 9, 7, 0, 10,
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 6, 10, 0, 0, 0, 0,
+    ];
+    #[rustfmt::skip]
+    let impossible = [
+        1, 0, 2,
+        0, 0, 0,
+        2, 0, 1,
+    ];
+
+    for solver in [&Backtracking as &dyn Solver, &Sat, &Parallel { threads: 2 }] {
+        let (grid, heads) = make_grid_and_pairs((10, 10), &mut points.into_iter());
+        let result = solver.solve(grid.into(), heads);
+        assert!(result.is_solved());
+        assert!(!result.grid.contains_empty());
+
+        let (grid, heads) = make_grid_and_pairs((3, 3), &mut impossible.into_iter());
+        assert!(matches!(
+            solver.solve(grid.into(), heads).status,
+            SolveStatus::Impossible { .. }
+        ));
+    }
+}
+
+#[test]
+fn test_count_solutions() {
+    #[rustfmt::skip]
+    let unique = [
+        0, 0, 0, 0,
+        0, 1, 0, 2,
+        0, 2, 0, 1,
+    ];
+    #[rustfmt::skip]
+    let ambiguous = [
+        0, 0, 1, 2,
+        0, 0, 0, 0,
+        1, 0, 0, 2,
+    ];
+
+    for solver in [&Backtracking as &dyn Solver, &Sat] {
+        let (grid, heads) = make_grid_and_pairs((4, 3), &mut unique.into_iter());
+        assert_eq!(solver.count_solutions(grid.into(), heads, 10), 1);
+
+        let (grid, heads) = make_grid_and_pairs((4, 3), &mut ambiguous.into_iter());
+        let found = solver.solutions(grid.clone().into(), heads.clone(), 10);
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().all(|g| !g.contains_empty()));
+        assert_eq!(solver.count_solutions(grid.into(), heads, 2), 2);
+    }
+
+    // stopped before it's known how many there are
+    let (grid, heads) = make_grid_and_pairs((4, 3), &mut ambiguous.into_iter());
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let options = SolveOptions {
+        cancel: Some(cancel),
+        ..Default::default()
+    };
+    assert_eq!(
+        solutions_with(grid.clone(), heads.clone(), 2, options),
+        None
+    );
+    let options = SolveOptions {
+        max_nodes: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(
+        solutions_with(grid.clone(), heads.clone(), 2, options),
+        None
+    );
+    let found = solutions_with(grid, heads, 2, SolveOptions::default());
+    assert_eq!(found.map(|found| found.len()), Some(2));
+}
+
+#[test]
+fn test_walls() {
+    #[rustfmt::skip]
+    let points = [
+        1, 0, 0, 1,
+        0, 0, 0, 0,
+    ];
+    let (mut grid, heads) = make_grid_and_pairs((4, 2), &mut points.into_iter());
+    // with the corners walled off the path has to dip down through the middle
+    grid[(0, 1)] = Cell::Wall;
+    grid[(3, 1)] = Cell::Wall;
+    let result = solve(grid, heads);
+
+    assert!(result.is_solved());
+    assert!(!result.grid.contains_empty());
+    assert_eq!(result.grid[(0, 1)], Cell::Wall);
+    assert_eq!(result.grid[(1, 1)], Cell::Colour(1));
+}
+
+#[test]
+fn test_bridges_and_warps() {
+    #[rustfmt::skip]
+    let points = [
+        0, 1, 0,
+        2, 0, 2,
+        0, 1, 0,
+    ];
+    let (mut grid, bridge_heads) = make_grid_and_pairs((3, 3), &mut points.into_iter());
+    for corner in [(0, 0), (2, 0), (0, 2), (2, 2)] {
+        grid[corner] = Cell::Wall;
+    }
+    grid[(1, 1)] = Cell::Bridge {
+        horizontal: 0,
+        vertical: 0,
+    };
+    let bridge = Board::new(grid);
+
+    // the ends are next to each other, so the only way to fill the board is to go
+    // round through the warp
+    let (grid, warp_heads) = make_grid_and_pairs((4, 1), &mut [0, 1, 1, 0].into_iter());
+    let mut warp = Board::new(grid);
+    warp.wrap_horizontally();
+    let mut blocked = warp.clone();
+    blocked.add_wall((0, 0), Side::Left);
+
+    for solver in [&Backtracking as &dyn Solver, &Sat] {
+        let result = solver.solve(bridge.clone(), bridge_heads.clone());
+        assert!(result.is_solved());
+        assert_eq!(
+            result.grid[(1, 1)],
+            Cell::Bridge {
+                horizontal: 2,
+                vertical: 1
+            }
+        );
+
+        let result = solver.solve(warp.clone(), warp_heads.clone());
+        assert!(result.is_solved());
+        assert!(!result.grid.contains_empty());
+
+        let result = solver.solve(blocked.clone(), warp_heads.clone());
+        assert!(!result.is_solved());
+    }
+}
+
+#[test]
+fn test_hex() {
+    // the path has to zig-zag through the diagonal only hex cells have, so a square
+    // board with the same cells can't be filled
+    let (grid, heads) = make_grid_and_pairs((2, 2), &mut [1, 0, 0, 1].into_iter());
+    let square = Board::new(grid.clone());
+    let hex = Board::with_topology(grid, &Hex);
+
+    for solver in [&Backtracking as &dyn Solver, &Sat] {
+        let result = solver.solve(hex.clone(), heads.clone());
+        assert!(result.is_solved());
+        assert!(!result.grid.contains_empty());
+        assert_eq!(solver.count_solutions(hex.clone(), heads.clone(), 10), 1);
+
+        assert!(!solver.solve(square.clone(), heads.clone()).is_solved());
+    }
 }
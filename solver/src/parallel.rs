@@ -0,0 +1,334 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use log::trace;
+
+use crate::{
+    control::Control,
+    search::{luby, Contradiction, Outcome, Puzzle, State, RESTART_UNIT},
+    solutions, solve_with, Array2D, Board, Heads, SolveOptions, SolveResult, Solver, Stats,
+    ThreadStats,
+};
+
+/// The same search as [`crate::Backtracking`] spread over several threads. Each guess
+/// leaves its other options behind as separate subtrees, which idle threads steal.
+/// How much each thread did ends up in [`Stats::threads`].
+///
+/// The same search can be run with [`SolveOptions`] through [`solve_with`] by setting
+/// [`SolveOptions::threads`].
+pub struct Parallel {
+    pub threads: usize,
+}
+
+impl Default for Parallel {
+    /// One thread for each core
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl Solver for Parallel {
+    /// Uses [`Self::threads`] in place of the ones in the options
+    fn solve_with(&self, board: Board, heads: Heads, options: SolveOptions) -> SolveResult {
+        let options = SolveOptions {
+            threads: self.threads,
+            ..options
+        };
+        solve_with(board, heads, options)
+    }
+
+    /// Finding every solution means searching the whole tree anyway, so this is left
+    /// to a single thread
+    fn solutions(&self, board: Board, heads: Heads, limit: usize) -> Vec<Array2D> {
+        solutions(board, heads, limit)
+    }
+}
+
+/// [`crate::search::search`] spread over `threads` threads. The calling thread is one
+/// of them, and is the one that keeps `control` up to date, while the others only
+/// stop when it says to.
+pub(crate) fn search(
+    puzzle: &Puzzle,
+    state: State,
+    control: &mut Control,
+    stats: &mut Stats,
+    threads: usize,
+) -> Outcome {
+    stats.threads = vec![ThreadStats::default(); threads.max(1)];
+    for run in 0.. {
+        if control.should_stop(stats.nodes) {
+            return Outcome::GaveUp;
+        }
+        // the other threads don't check the options, so they're held to the node limit
+        // by the budget
+        let budget = match control.max_nodes() {
+            Some(max) => (luby(run) * RESTART_UNIT).min(max - stats.nodes),
+            None => luby(run) * RESTART_UNIT,
+        };
+        match search_run(puzzle, state.clone(), run as u64, budget, control, stats) {
+            Some(Ok(solution)) => return Outcome::Solved(solution),
+            Some(Err(contradiction)) => return Outcome::Impossible(contradiction),
+            None if control.should_stop(stats.nodes) => return Outcome::GaveUp,
+            None => trace!("Restarting after {budget} nodes"),
+        }
+    }
+    unreachable!()
+}
+
+/// One run of the search spread over the threads, adding what each did to `stats`.
+/// Returns the solution, or the node of the last contradiction if there isn't one, or
+/// `None` if the threads between them went over `budget` nodes or `control` stopped
+/// them.
+fn search_run(
+    puzzle: &Puzzle,
+    state: State,
+    seed: u64,
+    budget: usize,
+    control: &mut Control,
+    stats: &mut Stats,
+) -> Option<Result<State, Option<usize>>> {
+    let shared = Shared {
+        puzzle,
+        seed,
+        queue: Injector::new(),
+        pending: AtomicUsize::new(1),
+        earlier_nodes: stats.nodes,
+        nodes: AtomicUsize::new(0),
+        budget,
+        stop: AtomicBool::new(false),
+        solution: Mutex::new(None),
+    };
+    shared.queue.push((state, 0));
+
+    let mut workers: Vec<_> = stats.threads.iter().map(|_| Worker::new_lifo()).collect();
+    let stealers: Vec<_> = workers.iter().map(Worker::stealer).collect();
+    let own = workers.remove(0);
+    let results: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|local| {
+                let (shared, stealers) = (&shared, &stealers);
+                scope.spawn(move || shared.work(local, stealers, None))
+            })
+            .collect();
+        let mut results = vec![shared.work(own, &stealers, Some(control))];
+        results.extend(handles.into_iter().map(|h| h.join().unwrap()));
+        results
+    });
+
+    let mut last_contradiction = None;
+    for (total, (run, contradiction)) in stats.threads.iter_mut().zip(results) {
+        stats.nodes += run.nodes;
+        stats.backtracks += run.backtracks;
+        total.nodes += run.nodes;
+        total.backtracks += run.backtracks;
+        total.subtrees += run.subtrees;
+        total.steals += run.steals;
+        last_contradiction = contradiction.or(last_contradiction);
+    }
+
+    if let Some(solution) = shared.solution.into_inner().unwrap() {
+        Some(Ok(solution))
+    } else if control.stopped() || shared.nodes.into_inner() >= budget {
+        None
+    } else {
+        Some(Err(last_contradiction))
+    }
+}
+
+/// Everything the threads share
+struct Shared<'a> {
+    puzzle: &'a Puzzle,
+    seed: u64,
+    /// Subtrees waiting to be searched, with how many guesses deep each one starts
+    queue: Injector<(State, usize)>,
+    /// Subtrees that have been handed out but not finished, the search is over once
+    /// this reaches 0
+    pending: AtomicUsize,
+    /// Nodes looked at in the runs before this one
+    earlier_nodes: usize,
+    /// Nodes looked at by all the threads together, the run is cut off once this
+    /// reaches `budget`
+    nodes: AtomicUsize,
+    budget: usize,
+    /// Set once any thread finds a solution, the budget runs out or the options say to
+    /// stop, telling the rest to stop
+    stop: AtomicBool,
+    solution: Mutex<Option<State>>,
+}
+
+impl Shared<'_> {
+    /// Searches subtrees until there are none left or a solution has been found.
+    /// Returns the node of the last contradiction this thread found. The thread given
+    /// `control` checks it as it goes, and stops everyone when it says to.
+    fn work(
+        &self,
+        local: Worker<(State, usize)>,
+        stealers: &[Stealer<(State, usize)>],
+        mut control: Option<&mut Control>,
+    ) -> (ThreadStats, Option<usize>) {
+        let mut stats = ThreadStats::default();
+        let mut last_contradiction = None;
+
+        while !self.stop.load(Ordering::Relaxed) {
+            let Some(subtree) = local
+                .pop()
+                .or_else(|| self.steal(&local, stealers, &mut stats))
+            else {
+                if self.pending.load(Ordering::Acquire) == 0 {
+                    break;
+                }
+                // nothing to search while the others finish theirs, but a cancel or
+                // deadline still has to be noticed
+                let nodes = self.earlier_nodes + self.nodes.load(Ordering::Relaxed);
+                if control.as_mut().is_some_and(|c| c.should_stop(nodes)) {
+                    self.stop.store(true, Ordering::Relaxed);
+                }
+                thread::yield_now();
+                continue;
+            };
+
+            if let Err(Contradiction(node, _)) =
+                self.dive(subtree, &local, &mut stats, control.as_deref_mut())
+            {
+                last_contradiction = Some(node);
+                stats.backtracks += 1;
+            }
+            stats.subtrees += 1;
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        }
+
+        (stats, last_contradiction)
+    }
+
+    fn steal(
+        &self,
+        local: &Worker<(State, usize)>,
+        stealers: &[Stealer<(State, usize)>],
+        stats: &mut ThreadStats,
+    ) -> Option<(State, usize)> {
+        loop {
+            let steal = self
+                .queue
+                .steal_batch_and_pop(local)
+                .or_else(|| stealers.iter().map(Stealer::steal).collect());
+            match steal {
+                Steal::Success(subtree) => {
+                    stats.steals += 1;
+                    return Some(subtree);
+                }
+                Steal::Empty => return None,
+                Steal::Retry => (),
+            }
+        }
+    }
+
+    /// Follows the likeliest move at each guess until the board is solved or stuck,
+    /// leaving the other moves for later
+    fn dive(
+        &self,
+        (mut state, mut depth): (State, usize),
+        local: &Worker<(State, usize)>,
+        stats: &mut ThreadStats,
+        mut control: Option<&mut Control>,
+    ) -> Result<(), Contradiction> {
+        let puzzle = self.puzzle;
+        while !self.stop.load(Ordering::Relaxed) {
+            let nodes = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+            let stopped = control
+                .as_mut()
+                .is_some_and(|c| c.tick(puzzle, &state, depth, self.earlier_nodes + nodes));
+            if stopped || nodes >= self.budget {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            stats.nodes += 1;
+            state.propagate(puzzle, None)?;
+
+            let Some(mut guess) = state.choose_guess(puzzle, self.seed) else {
+                trace!("Solving complete!");
+                *self.solution.lock().unwrap() = Some(state);
+                self.stop.store(true, Ordering::Relaxed);
+                return Ok(());
+            };
+            trace!("Guessing colour {} from {:?}", guess.colour, guess.options);
+            let mv = guess.options.pop().unwrap();
+            depth += 1;
+            // the likeliest moves are at the back, so these come off the local queue in
+            // the same order as they would from the undo stack
+            for other in guess.options {
+                let mut branch = state.clone();
+                branch.apply(puzzle, guess.colour, guess.side, other);
+                self.pending.fetch_add(1, Ordering::AcqRel);
+                local.push((branch, depth));
+            }
+            state.apply(puzzle, guess.colour, guess.side, mv);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parallel() {
+    use crate::{make_grid_and_pairs, CancelToken, SolveStatus};
+
+    #[rustfmt::skip]
+    let points = [
+        0, 0, 0, 0, 1, 2, 0, 0, 0, 2,
+        3, 0, 0, 0, 0, 0, 4, 5, 0, 0,
+        6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 7, 4, 0, 8, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 5, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 8,
+        0, 0, 0, 3, 1, 0, 9, 7, 0, 10,
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 6, 10, 0, 0, 0, 0,
+    ];
+    let (grid, heads) = make_grid_and_pairs((10, 10), &mut points.into_iter());
+
+    for threads in [1, 4] {
+        let options = SolveOptions {
+            threads,
+            ..Default::default()
+        };
+        let result = solve_with(grid.clone(), heads.clone(), options);
+        assert_eq!(result.status, SolveStatus::Solved);
+        assert!(!result.grid.contains_empty());
+        if threads > 1 {
+            assert_eq!(result.stats.threads.len(), threads);
+            let nodes: usize = result.stats.threads.iter().map(|t| t.nodes).sum();
+            assert_eq!(nodes, result.stats.nodes);
+            // every dive that went wrong finished a subtree, but not the other way
+            let backtracks: usize = result.stats.threads.iter().map(|t| t.backtracks).sum();
+            let subtrees: usize = result.stats.threads.iter().map(|t| t.subtrees).sum();
+            assert_eq!(backtracks, result.stats.backtracks);
+            assert!(backtracks <= subtrees);
+        }
+    }
+
+    // stopped early like the single threaded search
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let options = SolveOptions {
+        threads: 4,
+        cancel: Some(cancel),
+        ..Default::default()
+    };
+    let result = solve_with(grid.clone(), heads.clone(), options);
+    assert_eq!(result.status, SolveStatus::GaveUp);
+
+    let options = SolveOptions {
+        threads: 4,
+        max_nodes: Some(5),
+        ..Default::default()
+    };
+    assert_eq!(solve_with(grid, heads, options).status, SolveStatus::GaveUp);
+}
@@ -0,0 +1,98 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::{Array2D, IndexTy};
+
+/// Everything that came out of solving a board
+pub struct SolveResult {
+    /// The filled in grid if it was solved, otherwise the board as it was given
+    pub grid: Array2D,
+    pub status: SolveStatus,
+    pub stats: Stats,
+}
+
+impl SolveResult {
+    pub fn is_solved(&self) -> bool {
+        self.status == SolveStatus::Solved
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveStatus {
+    Solved,
+    /// The board doesn't describe a puzzle at all, usually from a misread dot
+    Malformed(Malformed),
+    /// Every possibility was tried and none of them work
+    Impossible {
+        /// Where the search last ran into a contradiction, if the solver tracks it
+        cell: Option<(IndexTy, IndexTy)>,
+    },
+    /// The search was stopped before it could finish
+    GaveUp,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Malformed {
+    /// Only one endpoint of this colour was found, `make_grid_and_pairs` pairs it
+    /// with `(-1, -1)`
+    MissingEndpoint { colour: u8, at: (IndexTy, IndexTy) },
+    /// An endpoint lies outside the board
+    OutOfBounds((IndexTy, IndexTy)),
+    /// Both ends of a pair are the same cell, or their cells don't match the colour
+    BadPair((IndexTy, IndexTy), (IndexTy, IndexTy)),
+    /// The same colour is used by more than one pair
+    DuplicateColour(u8),
+    /// A coloured cell that isn't the end of any pair, like a third endpoint
+    StrayCell((IndexTy, IndexTy)),
+}
+
+/// How much work the search did
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Board states looked at
+    pub nodes: usize,
+    /// Times a guess turned out wrong and the next one was tried
+    pub backtracks: usize,
+    pub elapsed: Duration,
+    /// What each thread did, for solvers that use more than one
+    pub threads: Vec<ThreadStats>,
+}
+
+/// How much work one thread of a parallel search did
+#[derive(Clone, Debug, Default)]
+pub struct ThreadStats {
+    pub nodes: usize,
+    /// Subtrees that ended in a contradiction, so the thread went on to another one
+    pub backtracks: usize,
+    /// Subtrees finished, whether solved or not
+    pub subtrees: usize,
+    /// Subtrees taken from the shared queue or another thread
+    pub steals: usize,
+}
+
+impl Display for SolveStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveStatus::Solved => write!(f, "Solved"),
+            SolveStatus::Malformed(reason) => write!(f, "Malformed board: {reason}"),
+            SolveStatus::Impossible { cell: Some(cell) } => {
+                write!(f, "Impossible, stuck at {cell:?}")
+            }
+            SolveStatus::Impossible { cell: None } => write!(f, "Impossible"),
+            SolveStatus::GaveUp => write!(f, "Gave up"),
+        }
+    }
+}
+
+impl Display for Malformed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Malformed::MissingEndpoint { colour, at } => {
+                write!(f, "colour {colour} at {at:?} has no other end")
+            }
+            Malformed::OutOfBounds(at) => write!(f, "endpoint {at:?} is off the board"),
+            Malformed::BadPair(a, b) => write!(f, "{a:?} and {b:?} can't be joined"),
+            Malformed::DuplicateColour(colour) => write!(f, "colour {colour} is used twice"),
+            Malformed::StrayCell(at) => write!(f, "{at:?} is coloured but not an endpoint"),
+        }
+    }
+}
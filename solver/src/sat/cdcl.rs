@@ -0,0 +1,347 @@
+//! A small conflict driven clause learning SAT solver: two watched literals, first
+//! UIP learning, VSIDS style variable activity, phase saving and Luby restarts
+
+use crate::search::luby;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn new(var: usize, positive: bool) -> Self {
+        Self((var as u32) << 1 | !positive as u32)
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::ops::Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+const CONFLICTS_PER_RESTART: usize = 100;
+const ACTIVITY_DECAY: f64 = 0.95;
+
+pub struct Cdcl {
+    clauses: Vec<Vec<Lit>>,
+    /// Clauses to look at again when each literal becomes true, as that makes one of
+    /// the two literals they watch false
+    watches: Vec<Vec<usize>>,
+
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    propagated: usize,
+
+    activity: Vec<f64>,
+    bump: f64,
+    saved_phase: Vec<bool>,
+
+    /// An empty clause was added, so nothing can ever satisfy this formula
+    unsatisfiable: bool,
+
+    pub decisions: usize,
+    pub conflicts: usize,
+}
+
+impl Cdcl {
+    pub fn new(var_count: usize) -> Self {
+        Self {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); var_count * 2],
+            values: vec![None; var_count],
+            levels: vec![0; var_count],
+            reasons: vec![None; var_count],
+            trail: Vec::new(),
+            trail_lim: Vec::new(),
+            propagated: 0,
+            activity: vec![0.; var_count],
+            bump: 1.,
+            saved_phase: vec![false; var_count],
+            unsatisfiable: false,
+            decisions: 0,
+            conflicts: 0,
+        }
+    }
+
+    fn lit_value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|v| v == lit.is_positive())
+    }
+
+    pub fn value(&self, var: usize) -> bool {
+        self.values[var] == Some(true)
+    }
+
+    fn level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    /// Adds a clause between calls to [`Cdcl::solve`], undoing any assignments left
+    /// over from the previous call
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        self.backtrack(0);
+
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for &lit in lits {
+            match self.lit_value(lit) {
+                Some(true) => return,
+                Some(false) => (),
+                None if clause.contains(&!lit) => return,
+                None if !clause.contains(&lit) => clause.push(lit),
+                None => (),
+            }
+        }
+
+        match clause[..] {
+            [] => self.unsatisfiable = true,
+            [lit] => {
+                self.assign(lit, None);
+                if self.propagate().is_some() {
+                    self.unsatisfiable = true;
+                }
+            }
+            _ => {
+                self.watch(self.clauses.len(), &clause);
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    fn watch(&mut self, index: usize, clause: &[Lit]) {
+        self.watches[(!clause[0]).index()].push(index);
+        self.watches[(!clause[1]).index()].push(index);
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(lit.is_positive());
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Returns the clause that became false, if any
+    fn propagate(&mut self) -> Option<usize> {
+        while let Some(&lit) = self.trail.get(self.propagated) {
+            self.propagated += 1;
+
+            let mut watching = std::mem::take(&mut self.watches[lit.index()]);
+            let mut i = 0;
+            while i < watching.len() {
+                let index = watching[i];
+                let clause = &mut self.clauses[index];
+                // keep the literal that just became false in slot 1
+                if clause[0] == !lit {
+                    clause.swap(0, 1);
+                }
+
+                let first = clause[0];
+                if self.values[first.var()].map(|v| v == first.is_positive()) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+
+                let replacement = (2..clause.len()).find(|&j| {
+                    let l = clause[j];
+                    self.values[l.var()].map(|v| v == l.is_positive()) != Some(false)
+                });
+                if let Some(j) = replacement {
+                    clause.swap(1, j);
+                    let new_watch = !clause[1];
+                    self.watches[new_watch.index()].push(index);
+                    watching.swap_remove(i);
+                    continue;
+                }
+
+                i += 1;
+                match self.lit_value(first) {
+                    Some(false) => {
+                        self.watches[lit.index()].append(&mut watching);
+                        return Some(index);
+                    }
+                    _ => self.assign(first, Some(index)),
+                }
+            }
+            self.watches[lit.index()].append(&mut watching);
+        }
+
+        None
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+        let keep = self.trail_lim[level];
+        for lit in self.trail.drain(keep..) {
+            self.saved_phase[lit.var()] = lit.is_positive();
+            self.values[lit.var()] = None;
+            self.reasons[lit.var()] = None;
+        }
+        self.trail_lim.truncate(level);
+        self.propagated = self.propagated.min(keep);
+    }
+
+    /// Works out a learnt clause from a conflict, returning it with the asserting
+    /// literal first and the level to jump back to
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<Lit>, usize) {
+        let mut seen = vec![false; self.values.len()];
+        let mut learnt = vec![Lit(0)];
+        let mut open = 0;
+        let mut index = self.trail.len();
+
+        let asserting = loop {
+            for i in 0..self.clauses[conflict].len() {
+                let lit = self.clauses[conflict][i];
+                let var = lit.var();
+                if seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                seen[var] = true;
+                self.bump_activity(var);
+                if self.levels[var] == self.level() {
+                    open += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+
+            // walk back to the next literal of this level involved in the conflict
+            let lit = loop {
+                index -= 1;
+                if seen[self.trail[index].var()] {
+                    break self.trail[index];
+                }
+            };
+            open -= 1;
+            if open == 0 {
+                break !lit;
+            }
+            conflict = self.reasons[lit.var()].unwrap();
+        };
+        learnt[0] = asserting;
+
+        let mut jump_level = 0;
+        if learnt.len() > 1 {
+            let (best, _) = learnt
+                .iter()
+                .enumerate()
+                .skip(1)
+                .max_by_key(|(_, l)| self.levels[l.var()])
+                .unwrap();
+            learnt.swap(1, best);
+            jump_level = self.levels[learnt[1].var()];
+        }
+
+        (learnt, jump_level)
+    }
+
+    fn bump_activity(&mut self, var: usize) {
+        self.activity[var] += self.bump;
+        if self.activity[var] > 1e100 {
+            for a in self.activity.iter_mut() {
+                *a *= 1e-100;
+            }
+            self.bump *= 1e-100;
+        }
+    }
+
+    fn pick_branch(&self) -> Option<Lit> {
+        let mut best: Option<usize> = None;
+        for var in (0..self.values.len()).filter(|v| self.values[*v].is_none()) {
+            if best.is_none_or(|b| self.activity[var] > self.activity[b]) {
+                best = Some(var);
+            }
+        }
+        best.map(|var| Lit::new(var, self.saved_phase[var]))
+    }
+
+    /// Searches for an assignment satisfying every clause added so far. The values
+    /// can be read back with [`Cdcl::value`] if one was found. `stop` is asked before
+    /// every restart, with the decisions made so far, and gives `None` if it says to.
+    pub fn solve(&mut self, mut stop: impl FnMut(usize) -> bool) -> Option<bool> {
+        if self.unsatisfiable {
+            return Some(false);
+        }
+        self.backtrack(0);
+
+        let mut restarts = 0;
+        loop {
+            if stop(self.decisions) {
+                return None;
+            }
+            let mut conflicts_left = luby(restarts) * CONFLICTS_PER_RESTART;
+            loop {
+                if let Some(conflict) = self.propagate() {
+                    self.conflicts += 1;
+                    if self.level() == 0 {
+                        self.unsatisfiable = true;
+                        return Some(false);
+                    }
+                    let (learnt, jump_level) = self.analyze(conflict);
+                    self.backtrack(jump_level);
+                    if learnt.len() == 1 {
+                        self.assign(learnt[0], None);
+                    } else {
+                        let index = self.clauses.len();
+                        self.watch(index, &learnt);
+                        self.assign(learnt[0], Some(index));
+                        self.clauses.push(learnt);
+                    }
+                    self.bump /= ACTIVITY_DECAY;
+                    conflicts_left = conflicts_left.saturating_sub(1);
+                    continue;
+                }
+
+                if conflicts_left == 0 {
+                    break;
+                }
+                let Some(decision) = self.pick_branch() else {
+                    return Some(true);
+                };
+                self.decisions += 1;
+                self.trail_lim.push(self.trail.len());
+                self.assign(decision, None);
+            }
+
+            self.backtrack(0);
+            restarts += 1;
+        }
+    }
+}
+
+#[test]
+fn test_pigeonhole() {
+    // three pigeons can't fit in two holes
+    let var = |pigeon: usize, hole: usize| pigeon * 2 + hole;
+    let mut cdcl = Cdcl::new(6);
+    for pigeon in 0..3 {
+        cdcl.add_clause(&[
+            Lit::new(var(pigeon, 0), true),
+            Lit::new(var(pigeon, 1), true),
+        ]);
+    }
+    for hole in 0..2 {
+        for a in 0..3 {
+            for b in a + 1..3 {
+                cdcl.add_clause(&[Lit::new(var(a, hole), false), Lit::new(var(b, hole), false)]);
+            }
+        }
+    }
+    assert_eq!(cdcl.solve(|_| false), Some(false));
+}
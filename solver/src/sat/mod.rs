@@ -0,0 +1,306 @@
+//! Solving by turning the board into a boolean formula and handing it to a SAT solver
+//!
+//! Every cell gets one variable per colour and every pair of neighbouring cells gets
+//! a variable saying whether a path runs between them. The clauses then say that:
+//! - every cell has exactly one colour, and endpoints have their own colour
+//! - endpoints have exactly one path leaving them, all other cells exactly two
+//! - cells joined by a path have the same colour
+//!
+//! That still allows loops cut off from every endpoint, so any the solver comes up
+//! with are ruled out one at a time and it is asked again.
+
+mod cdcl;
+
+use std::time::Instant;
+
+use log::trace;
+
+use crate::{
+    control::Control,
+    search::{Puzzle, State},
+    Array2D, Board, Heads, SolveOptions, SolveResult, SolveStatus, Solver, Stats,
+};
+use cdcl::{Cdcl, Lit};
+
+/// Solves boards with a SAT solver, see the [module docs](self) for the encoding.
+/// Doesn't use any of the Flow specific tricks [`Backtracking`](crate::Backtracking)
+/// does, but holds up much better on big boards where those run out.
+///
+/// Of the [`SolveOptions`], only the cancel token, deadline and node limit are followed,
+/// with every decision the SAT solver makes counted as a node. They're checked each time
+/// it restarts.
+pub struct Sat;
+
+impl Solver for Sat {
+    fn solve_with(&self, board: Board, heads: Heads, mut options: SolveOptions) -> SolveResult {
+        let start = Instant::now();
+        let mut control = Control::new(&mut options, None);
+        let puzzle = Puzzle::new(&board, &heads);
+        let mut result = SolveResult {
+            grid: board.grid,
+            status: SolveStatus::Impossible { cell: None },
+            stats: Stats::default(),
+        };
+
+        match puzzle {
+            Err(reason) => result.status = SolveStatus::Malformed(reason),
+            Ok((puzzle, state)) => {
+                let mut encoding = Encoding::new(&puzzle, &state);
+                if let Some(colours) = encoding.next_solution(&mut control) {
+                    encoding.write_to(&puzzle, &colours, &mut result.grid);
+                    result.status = SolveStatus::Solved;
+                } else if control.stopped() {
+                    result.status = SolveStatus::GaveUp;
+                }
+                result.stats.nodes = encoding.cdcl.decisions;
+                result.stats.backtracks = encoding.cdcl.conflicts;
+            }
+        }
+
+        result.stats.elapsed = start.elapsed();
+        result
+    }
+
+    fn solutions(&self, board: Board, heads: Heads, limit: usize) -> Vec<Array2D> {
+        let mut found = Vec::new();
+        let Ok((puzzle, state)) = Puzzle::new(&board, &heads) else {
+            return found;
+        };
+
+        let mut options = SolveOptions::default();
+        let mut control = Control::new(&mut options, None);
+        let mut encoding = Encoding::new(&puzzle, &state);
+        while found.len() < limit {
+            let Some(colours) = encoding.next_solution(&mut control) else {
+                break;
+            };
+            let mut solution = board.grid.clone();
+            encoding.write_to(&puzzle, &colours, &mut solution);
+            found.push(solution);
+            encoding.block(&colours);
+        }
+
+        found
+    }
+}
+
+struct Encoding {
+    cdcl: Cdcl,
+    colour_count: usize,
+    /// Nodes that take part, everything else was already filled in
+    open: Vec<bool>,
+    endpoint: Vec<bool>,
+    edges: Vec<(usize, usize)>,
+    node_edges: Vec<Vec<usize>>,
+}
+
+impl Encoding {
+    fn new(puzzle: &Puzzle, state: &State) -> Self {
+        let adjacent = &puzzle.graph.adjacent;
+        let node_count = adjacent.len();
+        let colour_count = state.tips.len();
+
+        let mut endpoint_colour = vec![None; node_count];
+        for (colour, tips) in state.tips.iter().enumerate() {
+            endpoint_colour[tips[0]] = Some(colour);
+            endpoint_colour[tips[1]] = Some(colour);
+        }
+        let open: Vec<bool> = (0..node_count)
+            .map(|n| state.colours[n] == 0 || endpoint_colour[n].is_some())
+            .collect();
+
+        let mut edges = Vec::new();
+        let mut node_edges = vec![Vec::new(); node_count];
+        for (a, around) in adjacent.iter().enumerate() {
+            for &b in around.iter().filter(|b| a < **b && open[a] && open[**b]) {
+                node_edges[a].push(edges.len());
+                node_edges[b].push(edges.len());
+                edges.push((a, b));
+            }
+        }
+
+        let mut encoding = Self {
+            cdcl: Cdcl::new(node_count * colour_count + edges.len()),
+            colour_count,
+            endpoint: endpoint_colour.iter().map(|c| c.is_some()).collect(),
+            open: open.clone(),
+            edges: edges.clone(),
+            node_edges,
+        };
+
+        for node in (0..node_count).filter(|n| open[*n]) {
+            let colours: Vec<Lit> = (0..colour_count)
+                .map(|c| encoding.colour(node, c, true))
+                .collect();
+            encoding.exactly(&colours, 1);
+
+            let edges: Vec<Lit> = encoding.node_edges[node]
+                .iter()
+                .map(|e| encoding.edge(*e, true))
+                .collect();
+            match endpoint_colour[node] {
+                Some(colour) => {
+                    let lit = encoding.colour(node, colour, true);
+                    encoding.cdcl.add_clause(&[lit]);
+                    encoding.exactly(&edges, 1);
+                }
+                None => encoding.exactly(&edges, 2),
+            }
+        }
+
+        for (e, &(a, b)) in edges.iter().enumerate() {
+            for colour in 0..colour_count {
+                let used = encoding.edge(e, false);
+                let (a_pos, a_neg) = (
+                    encoding.colour(a, colour, true),
+                    encoding.colour(a, colour, false),
+                );
+                let (b_pos, b_neg) = (
+                    encoding.colour(b, colour, true),
+                    encoding.colour(b, colour, false),
+                );
+                encoding.cdcl.add_clause(&[used, a_neg, b_pos]);
+                encoding.cdcl.add_clause(&[used, a_pos, b_neg]);
+            }
+        }
+
+        encoding
+    }
+
+    fn write_to(&self, puzzle: &Puzzle, colours: &[Option<usize>], grid: &mut Array2D) {
+        for (node, &colour) in colours.iter().enumerate() {
+            if let Some(colour) = colour {
+                puzzle.graph.write(grid, node, puzzle.ids[colour]);
+            }
+        }
+    }
+
+    fn colour(&self, node: usize, colour: usize, positive: bool) -> Lit {
+        Lit::new(node * self.colour_count + colour, positive)
+    }
+
+    fn edge(&self, edge: usize, positive: bool) -> Lit {
+        Lit::new(self.open.len() * self.colour_count + edge, positive)
+    }
+
+    fn exactly(&mut self, lits: &[Lit], count: usize) {
+        if lits.len() < count {
+            self.cdcl.add_clause(&[]);
+            return;
+        }
+        // no `count + 1` of them can all be true...
+        for subset in subsets(lits, count + 1) {
+            let clause: Vec<Lit> = subset.into_iter().map(|l| !l).collect();
+            self.cdcl.add_clause(&clause);
+        }
+        // ...and no `len - count + 1` of them can all be false
+        for subset in subsets(lits, lits.len() - count + 1) {
+            self.cdcl.add_clause(&subset);
+        }
+    }
+
+    /// Returns the colour of every node taking part, or `None` if there are no
+    /// solutions left or `control` stopped it first
+    fn next_solution(&mut self, control: &mut Control) -> Option<Vec<Option<usize>>> {
+        loop {
+            if !self
+                .cdcl
+                .solve(|decisions| control.should_stop(decisions))?
+            {
+                return None;
+            }
+
+            let loops = self.find_loops();
+            if loops.is_empty() {
+                break;
+            }
+            trace!("Ruling out {} loops", loops.len());
+            for edges in loops {
+                let clause: Vec<Lit> = edges.into_iter().map(|e| self.edge(e, false)).collect();
+                self.cdcl.add_clause(&clause);
+            }
+        }
+
+        let colours = (0..self.open.len())
+            .map(|node| {
+                (0..self.colour_count).find(|c| self.cdcl.value(self.colour(node, *c, true).var()))
+            })
+            .collect();
+        Some(colours)
+    }
+
+    /// Rules out a solution so the next call finds a different one. Only the colours
+    /// are blocked, as routing the same colours differently gives the same grid.
+    fn block(&mut self, colours: &[Option<usize>]) {
+        let clause: Vec<Lit> = colours
+            .iter()
+            .enumerate()
+            .filter(|(node, _)| !self.endpoint[*node])
+            .filter_map(|(node, colour)| colour.map(|c| self.colour(node, c, false)))
+            .collect();
+        self.cdcl.add_clause(&clause);
+    }
+
+    /// Groups of used edges that form loops not connected to any endpoint
+    fn find_loops(&self) -> Vec<Vec<usize>> {
+        let used: Vec<bool> = (0..self.edges.len())
+            .map(|e| self.cdcl.value(self.edge(e, true).var()))
+            .collect();
+        let mut visited = vec![false; self.open.len()];
+        let mut loops = Vec::new();
+
+        // everything reachable from an endpoint is part of a proper path
+        let starts: Vec<usize> = (0..self.open.len()).filter(|n| self.endpoint[*n]).collect();
+        for start in starts {
+            self.walk(start, &used, &mut visited);
+        }
+
+        for start in 0..self.open.len() {
+            if self.open[start] && !visited[start] {
+                loops.push(self.walk(start, &used, &mut visited));
+            }
+        }
+
+        loops
+    }
+
+    /// Marks every node connected to `start` by used edges, returning those edges
+    fn walk(&self, start: usize, used: &[bool], visited: &mut [bool]) -> Vec<usize> {
+        let mut edges = Vec::new();
+        let mut stack = vec![start];
+        visited[start] = true;
+
+        while let Some(node) = stack.pop() {
+            for &e in self.node_edges[node].iter().filter(|e| used[**e]) {
+                let (a, b) = self.edges[e];
+                let next = if a == node { b } else { a };
+                if !visited[next] {
+                    visited[next] = true;
+                    edges.push(e);
+                    stack.push(next);
+                } else if !edges.contains(&e) {
+                    edges.push(e);
+                }
+            }
+        }
+
+        edges
+    }
+}
+
+/// Every way of picking `size` of the items
+fn subsets(items: &[Lit], size: usize) -> Vec<Vec<Lit>> {
+    if size == 0 {
+        return vec![Vec::new()];
+    }
+    if items.len() < size {
+        return Vec::new();
+    }
+
+    let mut with_first = subsets(&items[1..], size - 1);
+    for subset in with_first.iter_mut() {
+        subset.insert(0, items[0]);
+    }
+    with_first.extend(subsets(&items[1..], size));
+    with_first
+}
@@ -0,0 +1,850 @@
+use std::ops::ControlFlow;
+
+use log::trace;
+
+use crate::{
+    control::Control,
+    explain::{Reason, Rule, Step},
+    graph::Graph,
+    Array2D, Board, Heads, IndexTy, Malformed, Stats,
+};
+
+/// The parts of a board that never change while solving
+pub(crate) struct Puzzle {
+    pub graph: Graph,
+    /// Grid value used for each colour, in `heads` order
+    pub ids: Vec<u8>,
+    /// Chessboard colouring of the cells, if the board has one
+    pub parity: Option<Vec<bool>>,
+}
+
+/// Everything the search changes
+pub(crate) struct State {
+    pub colours: Vec<u8>,
+    /// Where the two half-paths of each colour currently end
+    pub tips: Vec<[usize; 2]>,
+    pub done: Vec<bool>,
+    /// Every change made so far, for backtracking to undo
+    trail: Vec<Change>,
+}
+
+/// A copy has nothing to undo, it starts from wherever the state it's copied from is
+impl Clone for State {
+    fn clone(&self) -> Self {
+        Self {
+            colours: self.colours.clone(),
+            tips: self.tips.clone(),
+            done: self.done.clone(),
+            trail: Vec::new(),
+        }
+    }
+}
+
+/// Something [`State::apply`] did
+#[derive(Clone, Copy)]
+enum Change {
+    /// `node` was filled in by moving the tip on `side` there from `from`
+    Extend {
+        colour: usize,
+        side: usize,
+        from: usize,
+        node: usize,
+    },
+    Join(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Move {
+    Extend(usize),
+    Join,
+}
+
+/// Node at which a board was shown to be unsolvable, and why
+pub(crate) struct Contradiction(pub usize, pub Reason);
+
+pub(crate) struct Guess {
+    pub colour: usize,
+    pub side: usize,
+    pub options: Vec<Move>,
+}
+
+impl Puzzle {
+    pub fn new(board: &Board, heads: &Heads) -> Result<(Self, State), Malformed> {
+        let grid = &board.grid;
+        let graph = Graph::from_board(board);
+        let colours: Vec<u8> = (0..graph.positions.len())
+            .map(|n| graph.read(grid, n))
+            .collect();
+
+        let mut ids = Vec::with_capacity(heads.len());
+        let mut tips = Vec::with_capacity(heads.len());
+        let node = |pos| match grid.in_bounds(pos) {
+            true => Ok(graph.index_of(pos)),
+            false => Err(Malformed::OutOfBounds(pos)),
+        };
+        for &(a_pos, b_pos) in heads.iter() {
+            let a = node(a_pos)?;
+            if b_pos == (-1, -1) {
+                return Err(Malformed::MissingEndpoint {
+                    colour: grid[a_pos].colour().unwrap_or(0),
+                    at: a_pos,
+                });
+            }
+            // walls and bridges can't be endpoints
+            let (Some(a), Some(b)) = (a, node(b_pos)?) else {
+                return Err(Malformed::BadPair(a_pos, b_pos));
+            };
+            if a == b || colours[a] == 0 || colours[a] != colours[b] {
+                return Err(Malformed::BadPair(a_pos, b_pos));
+            }
+            if ids.contains(&colours[a]) {
+                return Err(Malformed::DuplicateColour(colours[a]));
+            }
+            ids.push(colours[a]);
+            tips.push([a, b]);
+        }
+
+        let is_head = |node| tips.iter().any(|t: &[usize; 2]| t.contains(&node));
+        if let Some(stray) = (0..colours.len()).find(|n| colours[*n] != 0 && !is_head(*n)) {
+            return Err(Malformed::StrayCell(graph.positions[stray]));
+        }
+
+        let state = State {
+            colours,
+            tips,
+            done: vec![false; heads.len()],
+            trail: Vec::new(),
+        };
+        let parity = two_colouring(&graph);
+        Ok((Self { graph, ids, parity }, state))
+    }
+
+    /// Where a move goes, as written in a [`Step`]
+    pub fn target(&self, mv: Move) -> Option<(IndexTy, IndexTy)> {
+        match mv {
+            Move::Extend(node) => Some(self.graph.positions[node]),
+            Move::Join => None,
+        }
+    }
+}
+
+fn two_colouring(graph: &Graph) -> Option<Vec<bool>> {
+    let mut colouring = vec![None; graph.adjacent.len()];
+    let mut queue = Vec::new();
+
+    for start in 0..graph.adjacent.len() {
+        if colouring[start].is_some() {
+            continue;
+        }
+        colouring[start] = Some(false);
+        queue.push(start);
+        while let Some(node) = queue.pop() {
+            let other = colouring[node].map(|x| !x);
+            for &n in graph.adjacent[node].iter() {
+                match colouring[n] {
+                    None => {
+                        colouring[n] = other;
+                        queue.push(n);
+                    }
+                    Some(_) if colouring[n] != other => return None,
+                    Some(_) => (),
+                }
+            }
+        }
+    }
+
+    colouring.into_iter().collect()
+}
+
+impl State {
+    pub fn apply(&mut self, puzzle: &Puzzle, colour: usize, side: usize, mv: Move) {
+        match mv {
+            Move::Extend(node) => {
+                self.trail.push(Change::Extend {
+                    colour,
+                    side,
+                    from: self.tips[colour][side],
+                    node,
+                });
+                self.colours[node] = puzzle.ids[colour];
+                self.tips[colour][side] = node;
+            }
+            Move::Join => {
+                self.trail.push(Change::Join(colour));
+                self.done[colour] = true;
+            }
+        }
+    }
+
+    /// A point to go back to with [`State::undo_to`]
+    pub fn mark(&self) -> usize {
+        self.trail.len()
+    }
+
+    /// Undoes every change made since `mark`, latest first
+    pub fn undo_to(&mut self, mark: usize) {
+        while self.trail.len() > mark {
+            match self.trail.pop().unwrap() {
+                Change::Extend {
+                    colour,
+                    side,
+                    from,
+                    node,
+                } => {
+                    self.colours[node] = 0;
+                    self.tips[colour][side] = from;
+                }
+                Change::Join(colour) => self.done[colour] = false,
+            }
+        }
+    }
+
+    pub fn write_to(&self, puzzle: &Puzzle, grid: &mut Array2D) {
+        for (node, &col) in self.colours.iter().enumerate() {
+            if col != 0 {
+                puzzle.graph.write(grid, node, col);
+            }
+        }
+    }
+
+    fn options<'a>(
+        &'a self,
+        puzzle: &'a Puzzle,
+        colour: usize,
+        side: usize,
+    ) -> impl Iterator<Item = Move> + 'a {
+        let tip = self.tips[colour][side];
+        let other = self.tips[colour][1 - side];
+
+        puzzle.graph.adjacent[tip].iter().filter_map(move |&n| {
+            if n == other {
+                Some(Move::Join)
+            } else if self.colours[n] == 0 {
+                Some(Move::Extend(n))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Makes every move that is forced, then checks the result for anything that can
+    /// no longer be completed. Forced moves are added to `log` if there is one.
+    pub fn propagate(
+        &mut self,
+        puzzle: &Puzzle,
+        mut log: Option<&mut Vec<Step>>,
+    ) -> Result<(), Contradiction> {
+        loop {
+            let mut progress_made = false;
+
+            // tips with only one way to go
+            for colour in 0..self.tips.len() {
+                for side in 0..2 {
+                    if self.done[colour] {
+                        break;
+                    }
+                    let mut options = self.options(puzzle, colour, side);
+                    let first_two = (options.next(), options.next());
+                    drop(options);
+                    match first_two {
+                        (None, _) => {
+                            return Err(Contradiction(self.tips[colour][side], Reason::Stuck))
+                        }
+                        (Some(only_option), None) => {
+                            if let Some(log) = log.as_deref_mut() {
+                                log.push(Step::Forced {
+                                    colour: puzzle.ids[colour],
+                                    cell: puzzle.target(only_option),
+                                    rule: Rule::OnlyMove,
+                                });
+                            }
+                            self.apply(puzzle, colour, side, only_option);
+                            progress_made = true;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            if !progress_made
+                && !self.fill_dead_ends(puzzle, log.as_deref_mut())?
+                && !self.avoid_bottlenecks(puzzle, &self.check(puzzle)?, log.as_deref_mut())?
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Which live tip, if any, ends at each node
+    fn live_tips(&self) -> Vec<Option<(usize, usize)>> {
+        let mut live_tips = vec![None; self.colours.len()];
+        for (colour, tips) in self.tips.iter().enumerate() {
+            if !self.done[colour] {
+                live_tips[tips[0]] = Some((colour, 0));
+                live_tips[tips[1]] = Some((colour, 1));
+            }
+        }
+        live_tips
+    }
+
+    /// Every empty cell gets used by a path going in and out of it, so one with only
+    /// two ways in or out must use both of them - if one of them is a tip, that tip
+    /// has to move into the cell
+    fn fill_dead_ends(
+        &mut self,
+        puzzle: &Puzzle,
+        log: Option<&mut Vec<Step>>,
+    ) -> Result<bool, Contradiction> {
+        let live_tips = self.live_tips();
+
+        for node in (0..self.colours.len()).filter(|n| self.colours[*n] == 0) {
+            let mut exits = [0; 2];
+            let mut exit_count = 0;
+            for &n in puzzle.graph.adjacent[node].iter() {
+                if self.colours[n] == 0 || live_tips[n].is_some() {
+                    if exit_count < 2 {
+                        exits[exit_count] = n;
+                    }
+                    exit_count += 1;
+                }
+            }
+            match exit_count {
+                0 | 1 => return Err(Contradiction(node, Reason::DeadEnd)),
+                2 => (),
+                _ => continue,
+            }
+
+            match (live_tips[exits[0]], live_tips[exits[1]]) {
+                (Some((a, _)), Some((b, _))) if a != b => {
+                    return Err(Contradiction(node, Reason::Bottleneck))
+                }
+                (Some((colour, side)), _) | (None, Some((colour, side))) => {
+                    if let Some(log) = log {
+                        log.push(Step::Forced {
+                            colour: puzzle.ids[colour],
+                            cell: Some(puzzle.graph.positions[node]),
+                            rule: Rule::Corner,
+                        });
+                    }
+                    self.apply(puzzle, colour, side, Move::Extend(node));
+                    return Ok(true);
+                }
+                (None, None) => (),
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// A tip that could go several ways, but only one of them isn't a cell another
+    /// colour can't do without, has to go that way
+    fn avoid_bottlenecks(
+        &mut self,
+        puzzle: &Puzzle,
+        needed_by: &[Option<usize>],
+        log: Option<&mut Vec<Step>>,
+    ) -> Result<bool, Contradiction> {
+        for colour in (0..self.tips.len()).filter(|c| !self.done[*c]) {
+            for side in 0..2 {
+                let mut options = self.options(puzzle, colour, side).filter(|mv| match mv {
+                    Move::Extend(n) => needed_by[*n].is_none_or(|other| other == colour),
+                    Move::Join => true,
+                });
+                let first_two = (options.next(), options.next());
+                drop(options);
+                match first_two {
+                    (None, _) => {
+                        return Err(Contradiction(self.tips[colour][side], Reason::Bottleneck))
+                    }
+                    (Some(only_option), None) => {
+                        if let Some(log) = log {
+                            log.push(Step::Forced {
+                                colour: puzzle.ids[colour],
+                                cell: puzzle.target(only_option),
+                                rule: Rule::Bottleneck,
+                            });
+                        }
+                        self.apply(puzzle, colour, side, only_option);
+                        return Ok(true);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Checks for anything that can no longer be completed, giving the colour that
+    /// each cell has to be filled by if it's a bottleneck for one
+    fn check(&self, puzzle: &Puzzle) -> Result<Vec<Option<usize>>, Contradiction> {
+        let adjacent = &puzzle.graph.adjacent;
+
+        if self.done.iter().all(|x| *x) {
+            return match self.colours.iter().position(|x| *x == 0) {
+                Some(empty) => Err(Contradiction(empty, Reason::Unfilled)),
+                None => Ok(vec![None; self.colours.len()]),
+            };
+        }
+
+        // every colour must still be able to reach its partner, and every region of
+        // empty cells must be reachable by both ends of some colour
+        let (region, region_count) = self.empty_regions(puzzle);
+        let mut users = vec![Vec::new(); region_count];
+        let mut choices = vec![0; self.tips.len()];
+        for colour in (0..self.tips.len()).filter(|c| !self.done[*c]) {
+            let [a, b] = self.tips[colour];
+            let touching_b: Vec<usize> = adjacent[b].iter().filter_map(|&n| region[n]).collect();
+            if adjacent[a].contains(&b) {
+                choices[colour] += 1;
+            }
+            for r in adjacent[a].iter().filter_map(|&n| region[n]) {
+                if touching_b.contains(&r) && !users[r].contains(&colour) {
+                    users[r].push(colour);
+                    choices[colour] += 1;
+                }
+            }
+            if choices[colour] == 0 {
+                return Err(Contradiction(a, Reason::Unreachable));
+            }
+        }
+
+        let mut sole_user = vec![false; self.tips.len()];
+        for (r, users) in users.iter().enumerate() {
+            let first_cell = region.iter().position(|x| *x == Some(r)).unwrap();
+            match users[..] {
+                [] => return Err(Contradiction(first_cell, Reason::Unfillable)),
+                [colour] if sole_user[colour] => {
+                    return Err(Contradiction(first_cell, Reason::Unfillable))
+                }
+                [colour] => sole_user[colour] = true,
+                _ => (),
+            }
+            if !self.parity_possible(puzzle, &region, r, users, &choices) {
+                return Err(Contradiction(first_cell, Reason::Parity));
+            }
+        }
+
+        self.check_bottlenecks(puzzle, &region)
+    }
+
+    /// On a board whose cells can be split into a chessboard pattern, a path has to
+    /// alternate between the two colours of cell. This fixes the difference between
+    /// how many of each a colour's path can cover, so the colours filling a region
+    /// have to add up to the difference in the region itself.
+    fn parity_possible(
+        &self,
+        puzzle: &Puzzle,
+        region: &[Option<usize>],
+        r: usize,
+        users: &[usize],
+        choices: &[usize],
+    ) -> bool {
+        let Some(parity) = &puzzle.parity else {
+            return true;
+        };
+
+        let imbalance: isize = (0..region.len())
+            .filter(|n| region[*n] == Some(r))
+            .map(|n| if parity[n] { 1 } else { -1 })
+            .sum();
+
+        let (mut low, mut high) = (0, 0);
+        for &colour in users {
+            let [a, b] = self.tips[colour];
+            let covers = match (parity[a], parity[b]) {
+                (true, true) => -1,
+                (false, false) => 1,
+                _ => 0,
+            };
+            if choices[colour] == 1 {
+                low += covers;
+                high += covers;
+            } else {
+                low += covers.min(0);
+                high += covers.max(0);
+            }
+        }
+
+        (low..=high).contains(&imbalance)
+    }
+
+    /// Labels each empty cell with the connected region of empty cells it is in
+    fn empty_regions(&self, puzzle: &Puzzle) -> (Vec<Option<usize>>, usize) {
+        let mut region = vec![None; self.colours.len()];
+        let mut count = 0;
+        let mut queue = Vec::new();
+
+        for start in 0..self.colours.len() {
+            if self.colours[start] != 0 || region[start].is_some() {
+                continue;
+            }
+            region[start] = Some(count);
+            queue.push(start);
+            while let Some(node) = queue.pop() {
+                for &n in puzzle.graph.adjacent[node].iter() {
+                    if self.colours[n] == 0 && region[n].is_none() {
+                        region[n] = Some(count);
+                        queue.push(n);
+                    }
+                }
+            }
+            count += 1;
+        }
+
+        (region, count)
+    }
+
+    /// A cut vertex of the empty cells that every path of a colour has to go through
+    /// belongs to that colour, so two colours can never both depend on the same one.
+    /// Gives the colour each cell belongs to, if any.
+    fn check_bottlenecks(
+        &self,
+        puzzle: &Puzzle,
+        region: &[Option<usize>],
+    ) -> Result<Vec<Option<usize>>, Contradiction> {
+        let mut needed_by = vec![None; self.colours.len()];
+        let cuts = self.cut_vertices(puzzle, region);
+        if !cuts.iter().any(|x| *x) {
+            return Ok(needed_by);
+        }
+
+        for colour in (0..self.tips.len()).filter(|c| !self.done[*c]) {
+            let [a, b] = self.tips[colour];
+            if puzzle.graph.adjacent[a].contains(&b) {
+                continue;
+            }
+            let Some(path) = self.empty_path(puzzle, a, b, None) else {
+                continue;
+            };
+            for node in path.into_iter().filter(|n| cuts[*n]) {
+                if self.empty_path(puzzle, a, b, Some(node)).is_some() {
+                    continue;
+                }
+                match needed_by[node] {
+                    Some(other) if other != colour => {
+                        return Err(Contradiction(node, Reason::Bottleneck))
+                    }
+                    _ => needed_by[node] = Some(colour),
+                }
+            }
+        }
+
+        Ok(needed_by)
+    }
+
+    /// Finds some path of empty cells leading from tip `a` to tip `b`
+    fn empty_path(
+        &self,
+        puzzle: &Puzzle,
+        a: usize,
+        b: usize,
+        blocked: Option<usize>,
+    ) -> Option<Vec<usize>> {
+        let adjacent = &puzzle.graph.adjacent;
+        let mut came_from = vec![usize::MAX; self.colours.len()];
+        let mut queue = std::collections::VecDeque::new();
+
+        for &n in adjacent[a].iter() {
+            if self.colours[n] == 0 && Some(n) != blocked {
+                came_from[n] = a;
+                queue.push_back(n);
+            }
+        }
+        while let Some(node) = queue.pop_front() {
+            if adjacent[node].contains(&b) {
+                let mut path = vec![node];
+                while came_from[*path.last().unwrap()] != a {
+                    path.push(came_from[*path.last().unwrap()]);
+                }
+                return Some(path);
+            }
+            for &n in adjacent[node].iter() {
+                if self.colours[n] == 0 && Some(n) != blocked && came_from[n] == usize::MAX {
+                    came_from[n] = node;
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Articulation points of the graph of empty cells
+    fn cut_vertices(&self, puzzle: &Puzzle, region: &[Option<usize>]) -> Vec<bool> {
+        let adjacent = &puzzle.graph.adjacent;
+        let len = self.colours.len();
+        let mut order = vec![usize::MAX; len];
+        let mut low = vec![0; len];
+        let mut is_cut = vec![false; len];
+        let mut counter = 0;
+
+        // iterative dfs, each frame is (node, parent, next neighbour to visit)
+        let mut stack: Vec<(usize, usize, usize)> = Vec::new();
+        for root in 0..len {
+            if region[root].is_none() || order[root] != usize::MAX {
+                continue;
+            }
+            order[root] = counter;
+            low[root] = counter;
+            counter += 1;
+            stack.push((root, usize::MAX, 0));
+            let mut root_children = 0;
+
+            while let Some(&mut (node, parent, ref mut next)) = stack.last_mut() {
+                if let Some(&child) = adjacent[node].get(*next) {
+                    *next += 1;
+                    if region[child].is_none() || child == parent {
+                        continue;
+                    }
+                    if order[child] == usize::MAX {
+                        order[child] = counter;
+                        low[child] = counter;
+                        counter += 1;
+                        if node == root {
+                            root_children += 1;
+                        }
+                        stack.push((child, node, 0));
+                    } else {
+                        low[node] = low[node].min(order[child]);
+                    }
+                } else {
+                    stack.pop();
+                    if parent != usize::MAX {
+                        low[parent] = low[parent].min(low[node]);
+                        if parent != root && low[node] >= order[parent] {
+                            is_cut[parent] = true;
+                        }
+                    }
+                }
+            }
+            is_cut[root] = root_children > 1;
+        }
+
+        is_cut
+    }
+
+    pub fn choose_guess(&self, puzzle: &Puzzle, seed: u64) -> Option<Guess> {
+        let mut best: Option<(Guess, u64)> = None;
+        for colour in (0..self.tips.len()).filter(|c| !self.done[*c]) {
+            for side in 0..2 {
+                let count = self.options(puzzle, colour, side).count();
+                let tie = tie_break(seed, colour * 2 + side);
+                if best
+                    .as_ref()
+                    .is_none_or(|(b, b_tie)| (count, tie) < (b.options.len(), *b_tie))
+                {
+                    let guess = Guess {
+                        colour,
+                        side,
+                        options: self.options(puzzle, colour, side).collect(),
+                    };
+                    best = Some((guess, tie));
+                }
+            }
+        }
+
+        // options are popped from the back, so put the likeliest last: joining up
+        // first, then cells that don't make the path touch itself, then cells with
+        // the fewest empty neighbours, and anything else at random
+        best.map(|(mut guess, _)| {
+            let id = puzzle.ids[guess.colour];
+            let tip = self.tips[guess.colour][guess.side];
+            guess.options.sort_by_key(|mv| match mv {
+                Move::Join => (0, 0, 0),
+                Move::Extend(node) => {
+                    let around = &puzzle.graph.adjacent[*node];
+                    let touches = around
+                        .iter()
+                        .filter(|n| **n != tip && self.colours[**n] == id)
+                        .count();
+                    let free = around.iter().filter(|n| self.colours[**n] == 0).count();
+                    (1 + touches, free, tie_break(seed, *node))
+                }
+            });
+            guess.options.reverse();
+            guess
+        })
+    }
+}
+
+/// Nodes searched before the first restart, later runs get multiples of this
+pub(crate) const RESTART_UNIT: usize = 100;
+
+pub(crate) enum Outcome {
+    Solved(State),
+    /// Holds the node of the last contradiction found
+    Impossible(Option<usize>),
+    GaveUp,
+}
+
+/// Depth first search over every choice of move, until `control` says to stop.
+///
+/// A bad guess early on can leave the search stuck in a huge dead subtree, so runs
+/// are cut off and restarted with a different tie-breaking order. The budgets follow
+/// the Luby sequence, which keeps growing without bound - eventually a run is given
+/// enough to finish, so a solution is always found if there is one.
+pub(crate) fn search(
+    puzzle: &Puzzle,
+    state: State,
+    control: &mut Control,
+    stats: &mut Stats,
+) -> Outcome {
+    for run in 0.. {
+        let budget = luby(run) * RESTART_UNIT;
+        let mut solution = None;
+        let result = search_run(
+            puzzle,
+            state.clone(),
+            run as u64,
+            budget,
+            control,
+            stats,
+            &mut |state| {
+                solution = Some(state.clone());
+                ControlFlow::Break(())
+            },
+        );
+        match (result, solution) {
+            (Ok(_), Some(solution)) => return Outcome::Solved(solution),
+            (Ok(contradiction), None) => return Outcome::Impossible(contradiction),
+            (Err(()), _) if control.stopped() => return Outcome::GaveUp,
+            (Err(()), _) => {
+                trace!("Restarting after {budget} nodes");
+                if let Some(log) = control.log() {
+                    log.push(Step::Restart);
+                }
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Calls `found` with every solution in turn until it breaks. This has to cover the
+/// whole search tree, so there are no restarts to help it along. Returns false if
+/// `control` stopped it before it got through them all.
+///
+/// Different branches can end up filling in the same grid by taking different routes,
+/// so the same solution may be seen more than once.
+pub(crate) fn enumerate(
+    puzzle: &Puzzle,
+    state: State,
+    control: &mut Control,
+    found: &mut impl FnMut(&State) -> ControlFlow<()>,
+) -> bool {
+    let mut stats = Stats::default();
+    search_run(puzzle, state, 0, usize::MAX, control, &mut stats, found).is_ok()
+}
+
+/// Returns the node of the last contradiction found, or `Err` if the budget ran out
+/// or `control` stopped the search before it finished
+fn search_run(
+    puzzle: &Puzzle,
+    mut state: State,
+    seed: u64,
+    mut budget: usize,
+    control: &mut Control,
+    stats: &mut Stats,
+    found: &mut impl FnMut(&State) -> ControlFlow<()>,
+) -> Result<Option<usize>, ()> {
+    // where to undo back to before each guess, and the moves left to try there
+    let mut undo_stack: Vec<(usize, usize, usize, Vec<Move>)> = Vec::new();
+    let mut last_contradiction = None;
+
+    loop {
+        if control.tick(puzzle, &state, undo_stack.len(), stats.nodes) {
+            return Err(());
+        }
+        budget = budget.checked_sub(1).ok_or(())?;
+        stats.nodes += 1;
+        let result = state.propagate(puzzle, control.log());
+
+        match result {
+            Ok(()) => match state.choose_guess(puzzle, seed) {
+                Some(mut guess) => {
+                    trace!("Guessing colour {} from {:?}", guess.colour, guess.options);
+                    let mv = guess.options.pop().unwrap();
+                    if let Some(log) = control.log() {
+                        log.push(Step::Guess {
+                            colour: puzzle.ids[guess.colour],
+                            cell: puzzle.target(mv),
+                            options: guess.options.len() + 1,
+                        });
+                    }
+                    undo_stack.push((state.mark(), guess.colour, guess.side, guess.options));
+                    state.apply(puzzle, guess.colour, guess.side, mv);
+                    continue;
+                }
+                None => {
+                    trace!("Solving complete!");
+                    if let Some(log) = control.log() {
+                        log.push(Step::Solved);
+                    }
+                    if found(&state).is_break() {
+                        return Ok(last_contradiction);
+                    }
+                }
+            },
+            Err(Contradiction(node, reason)) => {
+                trace!("Contradiction at {:?}", puzzle.graph.positions[node]);
+                if let Some(log) = control.log() {
+                    log.push(Step::Contradiction {
+                        cell: puzzle.graph.positions[node],
+                        reason,
+                    });
+                }
+                last_contradiction = Some(node);
+            }
+        }
+
+        loop {
+            let depth = undo_stack.len();
+            let Some((mark, colour, side, options)) = undo_stack.last_mut() else {
+                return Ok(last_contradiction);
+            };
+            if let Some(mv) = options.pop() {
+                trace!("Reverting grid and trying next");
+                stats.backtracks += 1;
+                if let Some(log) = control.log() {
+                    log.push(Step::Backtrack { depth });
+                    log.push(Step::Guess {
+                        colour: puzzle.ids[*colour],
+                        cell: puzzle.target(mv),
+                        options: options.len() + 1,
+                    });
+                }
+                state.undo_to(*mark);
+                state.apply(puzzle, *colour, *side, mv);
+                break;
+            }
+            trace!("No guesses made were correct - undoing guess");
+            undo_stack.pop();
+        }
+    }
+}
+
+/// 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ...
+pub(crate) fn luby(mut i: usize) -> usize {
+    let mut size = 1;
+    let mut power = 0;
+    while size < i + 1 {
+        power += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) / 2;
+        power -= 1;
+        i %= size;
+    }
+    1 << power
+}
+
+/// Pseudo-random number used to break ties differently on each restart
+fn tie_break(seed: u64, value: usize) -> u64 {
+    let mut x = seed
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        .wrapping_add(value as u64);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
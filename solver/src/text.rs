@@ -0,0 +1,255 @@
+//! Boards and solutions as plain text, one line per row and one character per cell.
+//!
+//! `.` is an empty cell, `#` a wall and `+` a bridge, anything else is a colour. A
+//! bridge with paths through it is written as its horizontal and vertical colours in
+//! brackets, like `(12)`. This also reads the text format most other Flow solvers use,
+//! where each colour is a letter such as `R` or `B`.
+
+use std::fmt::Display;
+
+use crate::{make_grid_and_pairs, Array2D, Cell, Heads};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// There were no rows
+    Empty,
+    /// A row isn't as long as the first one
+    RaggedRow {
+        row: usize,
+        len: usize,
+        expected: usize,
+    },
+    /// A character that isn't a cell, at `(x, y)` like a grid position
+    UnexpectedChar { c: char, at: (usize, usize) },
+    /// More colours than fit in a [`Cell`]
+    TooManyColours,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "no rows"),
+            ParseError::RaggedRow { row, len, expected } => {
+                write!(f, "row {row} is {len} cells long, expected {expected}")
+            }
+            ParseError::UnexpectedChar { c, at } => {
+                write!(f, "unexpected {c:?} at row {}, column {}", at.1, at.0)
+            }
+            ParseError::TooManyColours => write!(f, "more than 255 colours"),
+        }
+    }
+}
+
+/// What's written for one cell
+#[derive(Clone, Copy)]
+enum Written {
+    Char(char),
+    /// A bridge written with its colours
+    Bridge(Cell),
+}
+
+/// Splits the text into rows of cells, skipping blank lines and surrounding whitespace
+fn rows(text: &str) -> Result<Vec<Vec<Written>>, ParseError> {
+    let mut rows = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let y = rows.len();
+        let mut cells = Vec::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c != '(' {
+                cells.push(Written::Char(c));
+                continue;
+            }
+            let at = (cells.len(), y);
+            let bridge = match [chars.next(), chars.next(), chars.next()] {
+                [Some(h), Some(v), Some(')')] => Cell::from_bridge_chars(h, v),
+                _ => None,
+            };
+            cells.push(Written::Bridge(
+                bridge.ok_or(ParseError::UnexpectedChar { c, at })?,
+            ));
+        }
+        rows.push(cells);
+    }
+
+    let expected = rows.first().ok_or(ParseError::Empty)?.len();
+    for (row, cells) in rows.iter().enumerate() {
+        if cells.len() != expected {
+            return Err(ParseError::RaggedRow {
+                row,
+                len: cells.len(),
+                expected,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/// Reads an unsolved board. Colours are numbered in the order they first appear,
+/// whatever characters they're written as, so the pairs line up like they do from
+/// [`make_grid_and_pairs`].
+pub fn parse_board(text: &str) -> Result<(Array2D, Heads), ParseError> {
+    let rows = rows(text)?;
+    let size = (rows[0].len(), rows.len());
+
+    let mut seen: Vec<char> = Vec::new();
+    let mut points = Vec::with_capacity(size.0 * size.1);
+    let mut others = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &written) in row.iter().enumerate() {
+            let c = match written {
+                Written::Char(c) => c,
+                Written::Bridge(cell) => {
+                    points.push(0);
+                    others.push(((x as _, y as _), cell));
+                    continue;
+                }
+            };
+            match Cell::from_char(c) {
+                Some(Cell::Colour(_)) => (),
+                Some(Cell::Empty) => {
+                    points.push(0);
+                    continue;
+                }
+                Some(cell) => {
+                    points.push(0);
+                    others.push(((x as _, y as _), cell));
+                    continue;
+                }
+                None if c.is_alphanumeric() => (),
+                None => return Err(ParseError::UnexpectedChar { c, at: (x, y) }),
+            }
+            let colour = match seen.iter().position(|s| *s == c) {
+                Some(i) => i + 1,
+                None => {
+                    seen.push(c);
+                    seen.len()
+                }
+            };
+            points.push(u8::try_from(colour).map_err(|_| ParseError::TooManyColours)?);
+        }
+    }
+
+    let (mut grid, heads) = make_grid_and_pairs(size, &mut points.into_iter());
+    for (pos, cell) in others {
+        grid[pos] = cell;
+    }
+    Ok((grid, heads))
+}
+
+/// Reads a grid written by its [`Display`] impl, such as a solution, keeping the
+/// colour numbers as they were written
+pub fn parse_grid(text: &str) -> Result<Array2D, ParseError> {
+    let rows = rows(text)?;
+    let (width, height) = (rows[0].len(), rows.len());
+
+    let mut cells = Vec::with_capacity(width * height);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &written) in row.iter().enumerate() {
+            cells.push(match written {
+                Written::Char(c) => {
+                    Cell::from_char(c).ok_or(ParseError::UnexpectedChar { c, at: (x, y) })?
+                }
+                Written::Bridge(cell) => cell,
+            });
+        }
+    }
+    Ok(Array2D::from_vec(width, height, cells).expect("rows are all the same length"))
+}
+
+#[test]
+fn test_round_trip() {
+    let text = "\
+1..2.
+.#...
+...+.
+1.2..
+";
+    let (grid, heads) = parse_board(text).unwrap();
+    assert_eq!(heads, vec![((0, 0), (0, 3)), ((3, 0), (2, 3))]);
+    assert_eq!(grid[(1, 1)], Cell::Wall);
+    assert_eq!(grid.to_string(), text);
+    assert_eq!(parse_grid(text).unwrap(), grid);
+}
+
+#[test]
+fn test_bridge_colours() {
+    let text = "\
+.2.
+1(12)1
+.2.
+";
+    let grid = parse_grid(text).unwrap();
+    assert_eq!(
+        grid[(1, 1)],
+        Cell::Bridge {
+            horizontal: 1,
+            vertical: 2
+        }
+    );
+    assert_eq!(grid.to_string(), text);
+
+    // one path drawn so far, and an empty bridge
+    let text = "1(1.)1+\n";
+    let grid = parse_grid(text).unwrap();
+    assert_eq!(
+        grid[(1, 0)],
+        Cell::Bridge {
+            horizontal: 1,
+            vertical: 0
+        }
+    );
+    assert_eq!(grid.to_string(), text);
+
+    // a solved board with a bridge comes back the same
+    let (board, heads) = parse_board("#1#\n2+2\n#1#\n").unwrap();
+    let result = crate::solve(board, heads);
+    assert!(result.is_solved());
+    assert_eq!(result.grid.to_string(), "#1#\n2(21)2\n#1#\n");
+    assert_eq!(parse_grid(&result.grid.to_string()).unwrap(), result.grid);
+
+    assert_eq!(
+        parse_grid("1(1#)\n"),
+        Err(ParseError::UnexpectedChar { c: '(', at: (1, 0) })
+    );
+    assert_eq!(
+        parse_grid("1(12\n"),
+        Err(ParseError::UnexpectedChar { c: '(', at: (1, 0) })
+    );
+}
+
+#[test]
+fn test_letter_format() {
+    // a level written with a letter for each colour, as other solvers do
+    let text = "
+        RG...
+        ...BG
+        ..Y.B
+        ...YO
+        RO...
+    ";
+    let (grid, heads) = parse_board(text).unwrap();
+    assert_eq!(heads.len(), 5);
+    assert_eq!(grid[(1, 4)], Cell::Colour(5));
+
+    let result = crate::solve(grid, heads);
+    assert!(result.is_solved());
+    assert_eq!(parse_grid(&result.grid.to_string()).unwrap(), result.grid);
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse_board("\n  \n"), Err(ParseError::Empty));
+    assert_eq!(
+        parse_board("1..\n1.\n"),
+        Err(ParseError::RaggedRow {
+            row: 1,
+            len: 2,
+            expected: 3
+        })
+    );
+    assert_eq!(
+        parse_grid("1.\n.R\n.?\n"),
+        Err(ParseError::UnexpectedChar { c: '?', at: (1, 2) })
+    );
+}
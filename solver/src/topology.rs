@@ -0,0 +1,128 @@
+use std::fmt::Debug;
+
+use crate::IndexTy;
+
+/// One of the sides of a cell, which paths leave and enter through. Square cells
+/// only use the first four, hex cells use left and right along with the diagonals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Up,
+    Right,
+    Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Side {
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Up => Side::Down,
+            Side::Right => Side::Left,
+            Side::Down => Side::Up,
+            Side::UpLeft => Side::DownRight,
+            Side::UpRight => Side::DownLeft,
+            Side::DownLeft => Side::UpRight,
+            Side::DownRight => Side::UpLeft,
+        }
+    }
+
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Side::Left | Side::Right)
+    }
+}
+
+/// How the cells of a grid fit together: which sides each one has and what is on the
+/// other side of them
+pub trait Topology: Debug + Sync {
+    /// Every side of a cell, in the order neighbours are visited
+    fn sides(&self) -> &'static [Side];
+
+    /// The cell through `side` of `pos`, which may be off the grid
+    fn neighbour(&self, pos: (IndexTy, IndexTy), side: Side) -> (IndexTy, IndexTy);
+
+    /// Every cell next to `pos`, on the grid or not
+    fn around(&self, pos: (IndexTy, IndexTy)) -> Vec<(IndexTy, IndexTy)> {
+        self.sides()
+            .iter()
+            .map(|side| self.neighbour(pos, *side))
+            .collect()
+    }
+
+    /// Where the middle of the cell is, in cell widths from the middle of the first one
+    fn centre(&self, (x, y): (IndexTy, IndexTy)) -> (f64, f64) {
+        (x as f64, y as f64)
+    }
+}
+
+/// Square cells with four neighbours each
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Square;
+
+impl Topology for Square {
+    fn sides(&self) -> &'static [Side] {
+        &[Side::Left, Side::Up, Side::Right, Side::Down]
+    }
+
+    fn neighbour(&self, (x, y): (IndexTy, IndexTy), side: Side) -> (IndexTy, IndexTy) {
+        match side {
+            Side::Left => (x - 1, y),
+            Side::Up => (x, y - 1),
+            Side::Right => (x + 1, y),
+            Side::Down => (x, y + 1),
+            _ => panic!("Square cells have no {side:?} side"),
+        }
+    }
+}
+
+/// Pointy topped hexagons with six neighbours each. Rows are stored like a square
+/// grid with every odd row pushed half a cell to the right.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hex;
+
+impl Topology for Hex {
+    fn sides(&self) -> &'static [Side] {
+        &[
+            Side::Left,
+            Side::UpLeft,
+            Side::UpRight,
+            Side::Right,
+            Side::DownRight,
+            Side::DownLeft,
+        ]
+    }
+
+    fn neighbour(&self, (x, y): (IndexTy, IndexTy), side: Side) -> (IndexTy, IndexTy) {
+        // the column of the cells above and below depends on which way this row is shifted
+        let shift = y.rem_euclid(2);
+        match side {
+            Side::Left => (x - 1, y),
+            Side::Right => (x + 1, y),
+            Side::UpLeft => (x - 1 + shift, y - 1),
+            Side::UpRight => (x + shift, y - 1),
+            Side::DownLeft => (x - 1 + shift, y + 1),
+            Side::DownRight => (x + shift, y + 1),
+            _ => panic!("Hex cells have no {side:?} side"),
+        }
+    }
+
+    fn centre(&self, (x, y): (IndexTy, IndexTy)) -> (f64, f64) {
+        let shift = y.rem_euclid(2) as f64 / 2.0;
+        (x as f64 + shift, y as f64 * 3f64.sqrt() / 2.0)
+    }
+}
+
+#[test]
+fn test_hex_neighbours() {
+    for pos in [(2, 2), (2, 3)] {
+        for side in Hex.sides() {
+            let next = Hex.neighbour(pos, *side);
+            assert_eq!(Hex.neighbour(next, side.opposite()), pos);
+        }
+    }
+    assert_eq!(Hex.neighbour((2, 2), Side::UpLeft), (1, 1));
+    assert_eq!(Hex.neighbour((2, 3), Side::UpLeft), (2, 2));
+}
@@ -0,0 +1,267 @@
+use std::{
+    fmt::Display,
+    ops::{Index, IndexMut},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::get_around;
+
+pub type IndexTy = isize;
+
+/// A board of cells as the solver sees it
+pub type Array2D = Grid<Cell>;
+
+/// What a single square of the board holds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Cell {
+    #[default]
+    Empty,
+    /// Can't be drawn through
+    Wall,
+    /// Part of a path, colours are numbered from 1
+    Colour(u8),
+    /// Where two paths cross over, one left to right and one top to bottom. Either
+    /// is 0 until a path has been drawn through it.
+    Bridge { horizontal: u8, vertical: u8 },
+}
+
+/// Characters colours are written as, starting from colour 1
+const COLOUR_CHARS: &[u8] = b"123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+impl Cell {
+    pub fn colour(self) -> Option<u8> {
+        match self {
+            Cell::Colour(colour) => Some(colour),
+            _ => None,
+        }
+    }
+
+    /// How the cell is written in text, see [`crate::parse_grid`]. Bridges lose their
+    /// colours, which [`Display`] keeps, and colours past the 61 there are characters
+    /// for are written as `?`.
+    pub fn to_char(self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Wall => '#',
+            Cell::Bridge { .. } => '+',
+            Cell::Colour(colour) => COLOUR_CHARS
+                .get((colour as usize).wrapping_sub(1))
+                .map_or('?', |c| *c as char),
+        }
+    }
+
+    /// Reverses [`Cell::to_char`], also taking `0` as empty
+    pub fn from_char(c: char) -> Option<Cell> {
+        match c {
+            '.' | '0' => Some(Cell::Empty),
+            '#' => Some(Cell::Wall),
+            '+' => Some(Cell::Bridge {
+                horizontal: 0,
+                vertical: 0,
+            }),
+            _ => COLOUR_CHARS
+                .iter()
+                .position(|x| *x as char == c)
+                .map(|i| Cell::Colour(i as u8 + 1)),
+        }
+    }
+
+    /// A bridge with a path drawn through it, written as its horizontal and vertical
+    /// colours in brackets like `(12)`, with `.` for a path not drawn yet
+    pub fn from_bridge_chars(horizontal: char, vertical: char) -> Option<Cell> {
+        let colour = |c| match Cell::from_char(c)? {
+            Cell::Empty => Some(0),
+            Cell::Colour(colour) => Some(colour),
+            _ => None,
+        };
+        Some(Cell::Bridge {
+            horizontal: colour(horizontal)?,
+            vertical: colour(vertical)?,
+        })
+    }
+}
+
+impl Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Cell::Bridge {
+                horizontal,
+                vertical,
+            } if horizontal != 0 || vertical != 0 => {
+                let colour = |c| match c {
+                    0 => Cell::Empty,
+                    c => Cell::Colour(c),
+                };
+                write!(
+                    f,
+                    "({}{})",
+                    colour(horizontal).to_char(),
+                    colour(vertical).to_char()
+                )
+            }
+            cell => write!(f, "{}", cell.to_char()),
+        }
+    }
+}
+
+/// A rectangle of values stored row by row. Indexing outside it panics, use
+/// [`Grid::get`] where that can happen.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T: Clone + Default> Grid<T> {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![T::default(); width * height],
+            width,
+            height,
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    /// Builds a grid from its cells in reading order, or `None` if there aren't
+    /// exactly `width * height` of them
+    pub fn from_vec(width: usize, height: usize, cells: Vec<T>) -> Option<Self> {
+        (cells.len() == width * height).then_some(Self {
+            cells,
+            width,
+            height,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn in_bounds(&self, pos: (IndexTy, IndexTy)) -> bool {
+        pos.0 >= 0 && pos.1 >= 0 && pos.0 < self.width as IndexTy && pos.1 < self.height as IndexTy
+    }
+
+    fn offset(&self, pos: (IndexTy, IndexTy)) -> Option<usize> {
+        self.in_bounds(pos)
+            .then(|| pos.0 as usize + pos.1 as usize * self.width)
+    }
+
+    pub fn get(&self, pos: (IndexTy, IndexTy)) -> Option<&T> {
+        self.offset(pos).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, pos: (IndexTy, IndexTy)) -> Option<&mut T> {
+        self.offset(pos).map(|i| &mut self.cells[i])
+    }
+
+    /// Every cell in reading order
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    /// Every cell along with its position, in reading order
+    pub fn cells(&self) -> impl Iterator<Item = ((IndexTy, IndexTy), &T)> {
+        let width = self.width;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, cell)| (((i % width) as IndexTy, (i / width) as IndexTy), cell))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on 0, and a zero width grid has no rows anyway
+        self.cells.chunks(self.width.max(1))
+    }
+
+    /// Positions next to `pos` that are on the grid
+    pub fn neighbours(
+        &self,
+        pos: (IndexTy, IndexTy),
+    ) -> impl Iterator<Item = (IndexTy, IndexTy)> + '_ {
+        get_around(pos).into_iter().filter(|p| self.in_bounds(*p))
+    }
+}
+
+impl Grid<Cell> {
+    /// Whether any cell still needs a path drawn through it
+    pub fn contains_empty(&self) -> bool {
+        self.cells.iter().any(|cell| match cell {
+            Cell::Empty => true,
+            Cell::Bridge {
+                horizontal,
+                vertical,
+            } => *horizontal == 0 || *vertical == 0,
+            _ => false,
+        })
+    }
+}
+
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            for val in row {
+                write!(f, "{val}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Index<(IndexTy, IndexTy)> for Grid<T> {
+    type Output = T;
+
+    #[track_caller]
+    fn index(&self, index: (IndexTy, IndexTy)) -> &Self::Output {
+        match self.offset(index) {
+            Some(i) => &self.cells[i],
+            None => panic!(
+                "Index {index:?} out of bounds for {}x{} grid",
+                self.width, self.height
+            ),
+        }
+    }
+}
+
+impl<T> IndexMut<(IndexTy, IndexTy)> for Grid<T> {
+    #[track_caller]
+    fn index_mut(&mut self, index: (IndexTy, IndexTy)) -> &mut Self::Output {
+        match self.offset(index) {
+            Some(i) => &mut self.cells[i],
+            None => panic!(
+                "Index {index:?} out of bounds for {}x{} grid",
+                self.width, self.height
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_bounds() {
+    let mut grid: Grid<u8> = Grid::new(3, 2);
+    grid[(2, 1)] = 5;
+
+    assert_eq!(grid.get((2, 1)), Some(&5));
+    assert_eq!(grid.get((3, 0)), None);
+    assert_eq!(grid.get((0, 2)), None);
+    assert_eq!(grid.get((-1, 0)), None);
+    assert_eq!(grid.rows().count(), 2);
+    assert_eq!(grid.neighbours((0, 0)).count(), 2);
+}
+
+#[test]
+#[should_panic]
+fn test_write_past_end() {
+    let mut grid: Grid<u8> = Grid::new(3, 2);
+    grid[(0, 2)] = 1;
+}
@@ -1,7 +1,14 @@
-mod my_grid;
-pub use my_grid::*;
+mod grid;
+pub use grid::*;
 
+/// The four cells around `p` on a square board, [`crate::Topology::around`] works for
+/// any shape of cell
 #[inline]
 pub fn get_around(p: (IndexTy, IndexTy)) -> [(IndexTy, IndexTy); 4] {
-    [(p.0 - 1, p.1), (p.0, p.1 - 1), (p.0 + 1, p.1), (p.0, p.1 + 1)]
-}
\ No newline at end of file
+    [
+        (p.0 - 1, p.1),
+        (p.0, p.1 - 1),
+        (p.0 + 1, p.1),
+        (p.0, p.1 + 1),
+    ]
+}
@@ -0,0 +1,422 @@
+use std::fmt::Display;
+
+use crate::{graph::Graph, Array2D, Board, Cell, IndexTy};
+
+/// How many cells [`covering_path`] tries stepping into before giving up on a colour
+/// that runs alongside itself, since the number of ways through can grow
+/// exponentially with the size of the clump
+const PATH_STEPS: usize = 200_000;
+
+/// Why a grid isn't a solution to a board
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The solution isn't the same size as the board
+    WrongSize,
+    /// A cell the board gives, like an endpoint or a wall, is different in the solution
+    Changed((IndexTy, IndexTy)),
+    /// A cell wasn't filled in
+    Empty((IndexTy, IndexTy)),
+    /// The board doesn't have exactly two ends of this colour, so there's nothing to
+    /// check the path against
+    EndCount { colour: u8, count: usize },
+    /// The two ends of the colour aren't connected
+    Unjoined(u8),
+    /// A cell of the colour that isn't connected to its ends, like a separate loop
+    Stray { colour: u8, at: (IndexTy, IndexTy) },
+    /// The cells of the colour are connected but aren't one path, so it branches
+    /// somewhere
+    Branches(u8),
+    /// The colour runs alongside itself in so many ways that looking for one line
+    /// through all of its cells was given up on
+    Tangled(u8),
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::WrongSize => write!(f, "solution is a different size to the board"),
+            Violation::Changed(at) => write!(f, "{at:?} was changed"),
+            Violation::Empty(at) => write!(f, "{at:?} is empty"),
+            Violation::EndCount { colour, count } => {
+                write!(f, "colour {colour} has {count} ends on the board")
+            }
+            Violation::Unjoined(colour) => write!(f, "the ends of colour {colour} aren't joined"),
+            Violation::Stray { colour, at } => {
+                write!(f, "{at:?} is colour {colour} but not on its path")
+            }
+            Violation::Branches(colour) => write!(f, "the path of colour {colour} branches"),
+            Violation::Tangled(colour) => {
+                write!(f, "the path of colour {colour} is too tangled to check")
+            }
+        }
+    }
+}
+
+/// The cells one colour's path goes through, in order from one end to the other
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path {
+    pub colour: u8,
+    /// Both ends included. A bridge is in the paths of both colours going over it.
+    pub cells: Vec<(IndexTy, IndexTy)>,
+}
+
+/// Checks that `solution` solves `board`: nothing is left empty, the endpoints and
+/// walls are where they were, and each colour is a single path between its two ends.
+///
+/// A path can run alongside itself, which looks just like a branch in the grid, so
+/// cells with too many neighbours of their own colour are only a violation if there's
+/// no way to go through all of them in one line. Looking for that line can take a long
+/// time on big clumps of one colour, so it stops with [`Violation::Tangled`] if it
+/// hasn't found one after a while.
+pub fn validate(board: impl Into<Board>, solution: &Array2D) -> Result<(), Violation> {
+    trace_paths(board, solution).map(|_| ())
+}
+
+/// Like [`validate`], but gives the path of each colour that was checked, in colour
+/// order. Where a path runs alongside itself there's more than one way through its
+/// cells, and this is whichever was found first.
+pub fn trace_paths(board: impl Into<Board>, solution: &Array2D) -> Result<Vec<Path>, Violation> {
+    let board = board.into();
+    let grid = &board.grid;
+    if (grid.width(), grid.height()) != (solution.width(), solution.height()) {
+        return Err(Violation::WrongSize);
+    }
+
+    for ((pos, given), found) in grid.cells().zip(solution.iter()) {
+        let kept = match (given, found) {
+            (Cell::Empty, _) => true,
+            (Cell::Bridge { .. }, Cell::Bridge { .. }) => true,
+            _ => given == found,
+        };
+        if !kept {
+            return Err(Violation::Changed(pos));
+        }
+    }
+
+    let graph = Graph::from_board(&board);
+    let nodes = graph.positions.len();
+    let mut ends: Vec<Vec<usize>> = Vec::new();
+    for node in 0..nodes {
+        if graph.read(solution, node) == 0 {
+            return Err(Violation::Empty(graph.positions[node]));
+        }
+        let colour = graph.read(grid, node) as usize;
+        if colour != 0 {
+            if ends.len() <= colour {
+                ends.resize(colour + 1, Vec::new());
+            }
+            ends[colour].push(node);
+        }
+    }
+    for node in 0..nodes {
+        let colour = graph.read(solution, node) as usize;
+        if ends.get(colour).is_none_or(|e| e.is_empty()) {
+            return Err(Violation::EndCount {
+                colour: colour as u8,
+                count: 0,
+            });
+        }
+    }
+
+    let mut paths = Vec::new();
+    for (colour, ends) in ends.iter().enumerate().skip(1) {
+        let colour = colour as u8;
+        let (a, b) = match ends[..] {
+            [] => continue,
+            [a, b] => (a, b),
+            _ => {
+                return Err(Violation::EndCount {
+                    colour,
+                    count: ends.len(),
+                })
+            }
+        };
+        let nodes = check_path(&graph, solution, colour, a, b)?;
+        paths.push(Path {
+            colour,
+            cells: nodes.into_iter().map(|n| graph.positions[n]).collect(),
+        });
+    }
+    Ok(paths)
+}
+
+/// The nodes of the colour's path from `a` to `b`, if it is one
+fn check_path(
+    graph: &Graph,
+    solution: &Array2D,
+    colour: u8,
+    a: usize,
+    b: usize,
+) -> Result<Vec<usize>, Violation> {
+    let same = |node: usize| {
+        graph.adjacent[node]
+            .iter()
+            .copied()
+            .filter(move |n| graph.read(solution, *n) == colour)
+    };
+
+    // everything of the colour has to be reachable from one end. Sides alternate along
+    // the way, so a path through every cell steps between them each time unless there
+    // are odd loops somewhere.
+    let mut reached = vec![false; graph.positions.len()];
+    let mut side = vec![false; graph.positions.len()];
+    let mut two_sided = true;
+    reached[a] = true;
+    let mut queue = vec![a];
+    let mut cells = 1;
+    while let Some(node) = queue.pop() {
+        for n in same(node) {
+            if !reached[n] {
+                reached[n] = true;
+                side[n] = !side[node];
+                cells += 1;
+                queue.push(n);
+            } else if side[n] == side[node] {
+                two_sided = false;
+            }
+        }
+    }
+    if !reached[b] {
+        return Err(Violation::Unjoined(colour));
+    }
+    if let Some(stray) =
+        (0..reached.len()).find(|n| !reached[*n] && graph.read(solution, *n) == colour)
+    {
+        return Err(Violation::Stray {
+            colour,
+            at: graph.positions[stray],
+        });
+    }
+
+    // the usual case, where every cell along the way has exactly two neighbours on the
+    // path and the ends have one
+    let simple = (0..reached.len())
+        .filter(|n| reached[*n])
+        .all(|n| same(n).count() == if n == a || n == b { 1 } else { 2 });
+    let mut path = vec![a];
+    let mut visited = vec![false; graph.positions.len()];
+    if simple {
+        // there's only ever one way on
+        visited[a] = true;
+        while let Some(next) = same(*path.last().unwrap()).find(|n| !visited[*n]) {
+            visited[next] = true;
+            path.push(next);
+        }
+        return Ok(path);
+    }
+
+    if two_sided {
+        // a line through all the cells starts on a's side, so that side has to have
+        // either the same number of cells as the other with b across from a, or one
+        // more with b on the same side
+        let a_side = (0..reached.len())
+            .filter(|n| reached[*n] && !side[*n])
+            .count();
+        let balanced = if side[b] {
+            2 * a_side == cells
+        } else {
+            2 * a_side == cells + 1
+        };
+        if !balanced {
+            return Err(Violation::Branches(colour));
+        }
+    }
+
+    let mut steps = PATH_STEPS;
+    let found = covering_path(
+        graph,
+        solution,
+        colour,
+        b,
+        cells,
+        &mut path,
+        &mut visited,
+        &mut steps,
+    );
+    match found {
+        Some(true) => Ok(path),
+        Some(false) => Err(Violation::Branches(colour)),
+        None => Err(Violation::Tangled(colour)),
+    }
+}
+
+/// Whether there's a path on from the last node of `path` to `end` through `left` more
+/// cells of the colour, leaving it in `path` if there is, or `None` if `steps` ran out
+/// before finding out
+#[allow(clippy::too_many_arguments)]
+fn covering_path(
+    graph: &Graph,
+    solution: &Array2D,
+    colour: u8,
+    end: usize,
+    left: usize,
+    path: &mut Vec<usize>,
+    visited: &mut [bool],
+    steps: &mut usize,
+) -> Option<bool> {
+    let node = *path.last().unwrap();
+    if node == end {
+        return Some(left == 1);
+    }
+    *steps = steps.checked_sub(1)?;
+    visited[node] = true;
+    let open = |n: usize| !visited[n] && graph.read(solution, n) == colour;
+    // the cells next to this one besides the end have to be gone into and out of again,
+    // from here or from the cells of the colour still left next to them. One with none
+    // left would be a dead end, and one with only one left has to be next.
+    let left_around = |n: usize| graph.adjacent[n].iter().filter(|m| open(**m)).count();
+    let squeezed = |ways| {
+        graph.adjacent[node]
+            .iter()
+            .copied()
+            .filter(move |&n| n != end && open(n) && left_around(n) == ways)
+    };
+    let next: Vec<usize> = if squeezed(0).next().is_some() {
+        Vec::new()
+    } else if let Some(forced) = squeezed(1).next() {
+        vec![forced]
+    } else {
+        graph.adjacent[node]
+            .iter()
+            .copied()
+            .filter(|n| open(*n))
+            .collect()
+    };
+    for n in next {
+        path.push(n);
+        match covering_path(graph, solution, colour, end, left - 1, path, visited, steps) {
+            Some(false) => (),
+            found => return found,
+        }
+        path.pop();
+    }
+    visited[node] = false;
+    Some(false)
+}
+
+#[test]
+fn test_validate() {
+    use crate::{parse_board, parse_grid};
+
+    let check = |board: &str, solution: &str| {
+        let (grid, _) = parse_board(board).unwrap();
+        validate(grid, &parse_grid(solution).unwrap())
+    };
+
+    let board = "1...\n..2.\n.1..\n...2";
+    assert_eq!(check(board, "1111\n2221\n2111\n2222"), Ok(()));
+    assert_eq!(
+        check(board, "1111\n2221\n.111\n2222"),
+        Err(Violation::Empty((0, 2)))
+    );
+    assert_eq!(
+        check(board, "2111\n2221\n2111\n2222"),
+        Err(Violation::Changed((0, 0)))
+    );
+    assert_eq!(
+        check(board, "111\n222\n211\n222"),
+        Err(Violation::WrongSize)
+    );
+    assert_eq!(
+        check(board, "1111\n2221\n2111\n1222"),
+        Err(Violation::Stray {
+            colour: 1,
+            at: (0, 3)
+        })
+    );
+    assert_eq!(
+        check(board, "1111\n3321\n2111\n2222"),
+        Err(Violation::EndCount {
+            colour: 3,
+            count: 0
+        })
+    );
+
+    // running alongside itself is fine, as long as it can still be drawn in one line
+    assert_eq!(check("1.1\n...\n...", "111\n111\n111"), Ok(()));
+    assert_eq!(
+        check("11.\n...\n...", "111\n111\n111"),
+        Err(Violation::Branches(1))
+    );
+    assert_eq!(check("1.1\n2.2", "121\n212"), Err(Violation::Unjoined(1)));
+
+    // a big clump only needs a way through found, and the sides it alternates between
+    // can rule it out without looking
+    let clump = |ends: &str, under: &str| {
+        let board = format!("{ends}\n{}{under}", "..........\n".repeat(9));
+        let solution = format!("{}{under}", "1111111111\n".repeat(10));
+        check(&board, &solution.replace('.', "1"))
+    };
+    assert_eq!(clump("1........1", "##########"), Ok(()));
+    assert_eq!(
+        clump("1.1.......", "##########"),
+        Err(Violation::Branches(1))
+    );
+    // two cells sticking out that it could only end in, which there's no quick way to
+    // tell
+    assert_eq!(
+        clump("11........", "##.####.##"),
+        Err(Violation::Tangled(1))
+    );
+    assert_eq!(
+        check("1..\n...", "111\n111"),
+        Err(Violation::EndCount {
+            colour: 1,
+            count: 1
+        })
+    );
+
+    for (board, heads) in [
+        parse_board(board).unwrap(),
+        parse_board("1.1\n2.2").unwrap(),
+    ] {
+        let result = crate::solve(board.clone(), heads);
+        assert_eq!(validate(board, &result.grid), Ok(()));
+    }
+}
+
+#[test]
+fn test_trace_paths() {
+    use crate::{parse_board, parse_grid};
+
+    let paths = |board, solution| {
+        let (grid, _) = parse_board(board).unwrap();
+        trace_paths(grid, &parse_grid(solution).unwrap())
+    };
+
+    assert_eq!(paths("1.1\n2.2", "121\n212"), Err(Violation::Unjoined(1)));
+
+    let found = paths("1.1\n2.2", "111\n222").unwrap();
+    assert_eq!(
+        found,
+        vec![
+            Path {
+                colour: 1,
+                cells: vec![(0, 0), (1, 0), (2, 0)]
+            },
+            Path {
+                colour: 2,
+                cells: vec![(0, 1), (1, 1), (2, 1)]
+            },
+        ]
+    );
+
+    // running alongside itself, through every cell in a line that doesn't touch itself
+    for (board, ends) in [
+        ("1.1\n...\n...", [(0, 0), (2, 0)]),
+        ("1..\n...\n..1", [(0, 0), (2, 2)]),
+    ] {
+        let found = paths(board, "111\n111\n111").unwrap();
+        let cells = &found[0].cells;
+        assert_eq!(cells.len(), 9);
+        assert_eq!([cells[0], cells[8]], ends);
+        assert!(cells.windows(2).all(|step| {
+            let ((x, y), (nx, ny)) = (step[0], step[1]);
+            (x - nx).abs() + (y - ny).abs() == 1
+        }));
+        let mut unique = cells.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 9);
+    }
+}
@@ -0,0 +1,33 @@
+//! Boards shared by the integration tests and benchmarks
+
+use std::fs;
+
+use solver::{parse_board, Array2D, Heads};
+
+/// A board from `corpus/`, named after its file
+pub struct Case {
+    pub name: String,
+    pub grid: Array2D,
+    pub heads: Heads,
+}
+
+/// Every board in `corpus/<kind>`, sorted by name
+pub fn corpus(kind: &str) -> Vec<Case> {
+    let dir = format!("{}/corpus/{kind}", env!("CARGO_MANIFEST_DIR"));
+    let mut cases: Vec<Case> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("can't read {dir}: {e}"))
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let text = fs::read_to_string(&path).unwrap();
+            let (grid, heads) = parse_board(&text)
+                .unwrap_or_else(|e| panic!("can't parse {}: {e}", path.display()));
+            Case {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                grid,
+                heads,
+            }
+        })
+        .collect();
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    cases
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::corpus;
+use solver::{validate, SolveStatus};
+
+#[test]
+fn test_solvable() {
+    for case in corpus("solvable") {
+        let result = solver::solve(case.grid.clone(), case.heads.clone());
+        assert_eq!(result.status, SolveStatus::Solved, "{}", case.name);
+        if let Err(e) = validate(case.grid.clone(), &result.grid) {
+            panic!("{}: {e}\n{}", case.name, result.grid);
+        }
+    }
+}
+
+#[test]
+fn test_impossible() {
+    for case in corpus("impossible") {
+        let result = solver::solve(case.grid, case.heads);
+        assert!(
+            matches!(result.status, SolveStatus::Impossible { .. }),
+            "{}: {}",
+            case.name,
+            result.status
+        );
+    }
+}
+
+/// Slow without optimisations, run with `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn test_hard() {
+    for case in corpus("hard") {
+        let result = solver::solve(case.grid.clone(), case.heads.clone());
+        assert_eq!(result.status, SolveStatus::Solved, "{}", case.name);
+        if let Err(e) = validate(case.grid.clone(), &result.grid) {
+            panic!("{}: {e}\n{}", case.name, result.grid);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use proptest::{prelude::*, sample::subsequence};
+use rand::{rngs::StdRng, SeedableRng};
+use solver::{
+    make_grid_and_pairs, validate, Array2D, Backtracking, Board, Heads, Parallel, Sat, SolveStatus,
+    Solver,
+};
+
+/// Small boards with endpoints dotted around at random, which mostly have no solution
+/// or lots of them
+fn random_board() -> impl Strategy<Value = (Array2D, Heads)> {
+    (2..7usize, 2..7usize)
+        .prop_flat_map(|(width, height)| {
+            let cells = width * height;
+            let ends = subsequence((0..cells).collect::<Vec<_>>(), 2..=cells.min(12));
+            (Just((width, height)), ends.prop_shuffle())
+        })
+        .prop_map(|(size, ends)| {
+            let mut points = vec![0; size.0 * size.1];
+            for (i, pair) in ends.chunks_exact(2).enumerate() {
+                points[pair[0]] = i as u8 + 1;
+                points[pair[1]] = i as u8 + 1;
+            }
+            make_grid_and_pairs(size, &mut points.into_iter())
+        })
+}
+
+proptest! {
+    #[test]
+    fn solutions_are_valid((grid, heads) in random_board()) {
+        let solvers: [&dyn Solver; 3] = [&Backtracking, &Sat, &Parallel { threads: 2 }];
+        let results: Vec<_> = solvers
+            .iter()
+            .map(|s| s.solve(Board::new(grid.clone()), heads.clone()))
+            .collect();
+
+        for result in &results {
+            if result.is_solved() {
+                prop_assert_eq!(validate(grid.clone(), &result.grid), Ok(()));
+            }
+        }
+        // they can find different solutions, but not disagree on whether there is one
+        let solved: Vec<_> = results.iter().map(|r| r.is_solved()).collect();
+        prop_assert!(solved.iter().all(|s| *s == solved[0]), "{:?}", solved);
+    }
+
+    #[test]
+    fn every_solution_is_valid((grid, heads) in random_board()) {
+        for solution in solver::solutions(grid.clone(), heads.clone(), 20) {
+            prop_assert_eq!(validate(grid.clone(), &solution), Ok(()));
+        }
+    }
+
+    #[test]
+    fn generated_boards_solve(seed in any::<u64>(), size in 4..9usize) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        if let Some((grid, heads)) = solver::generate((size, size), size, &mut rng) {
+            let result = solver::solve(grid.clone(), heads.clone());
+            prop_assert_eq!(result.status, SolveStatus::Solved);
+            prop_assert_eq!(validate(grid.clone(), &result.grid), Ok(()));
+        }
+    }
+}